@@ -0,0 +1,25 @@
+use crate::{Asset, PriceConfidence};
+use soroban_sdk::Env;
+
+/// Interface for prices reported with a confidence interval. The confidence is the half-width
+/// of the interval around the price, in the same units, so consumers can act on the bounds
+/// `price - confidence` and `price + confidence` when reporters disagree or markets are thin.
+pub trait IsConfidenceFeed {
+    /// Get the most recent price for an asset with its confidence. Prices recorded without a
+    /// confidence, including those of derived assets, have a confidence of zero.
+    fn lastprice_confidence(env: &Env, asset: Asset) -> Option<PriceConfidence>;
+
+    /// Record a price with its confidence. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin address, if `confidence` is negative, or if
+    /// the price could not be written by `set_asset_price`
+    fn set_asset_price_with_confidence(
+        env: &Env,
+        asset: Asset,
+        price: i128,
+        confidence: i128,
+        timestamp: u64,
+    );
+}
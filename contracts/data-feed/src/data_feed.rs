@@ -1,10 +1,26 @@
 use soroban_sdk::{
     Address, BytesN, Env, Map, Symbol, Vec, contract, contracterror, contractimpl, contracttype,
-    panic_with_error, symbol_short, vec,
+    panic_with_error, symbol_short, token::TokenClient, xdr::ToXdr,
 };
 
-use crate::sep40::{IsSep40, IsSep40Admin};
-use crate::{Asset, PriceData};
+use crate::confidence::IsConfidenceFeed;
+use crate::delayed::IsDelayedFeed;
+use crate::derived::IsDerivedFeed;
+use crate::events::{
+    AdminChanged, AdminProposed, AssetDeprecated, AssetRemoved, AssetsAdded, DerivedAssetAdded,
+    HistoryPruned, PriceAggregated, PriceDelaySet, PriceForced, PriceGapDetected, PriceGuardSet,
+    PriceSubmitted, PriceUpdated, PricesUpdated, QuorumSet, ReporterBonded, ReporterSlashed,
+    ReporterUnbonded, ReporterUnbonding, ReportersAdded, ReportersRemoved, RetentionSet,
+    SignedPriceUpdated, SignersAdded, SignersRemoved,
+};
+use crate::formula::Formula;
+use crate::guard::PriceGuard;
+use crate::history::PriceHistory;
+use crate::reporters::{IsMedianFeed, IsStakedReporters};
+use crate::sep40::{IsSep40, IsSep40Admin, IsSep40Cross, IsSep40Twap};
+use crate::signed::IsSignedFeed;
+use crate::staking::{self, Bond, StakingConfig};
+use crate::{Asset, PriceConfidence, PriceData};
 
 #[contracterror]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
@@ -15,6 +31,95 @@ pub enum Error {
 
     /// Asset already exists
     AssetAlreadyExists = 2,
+
+    /// Address is not a whitelisted reporter
+    NotReporter = 3,
+
+    /// Reporter is already whitelisted
+    ReporterAlreadyExists = 4,
+
+    /// Quorum must be between 1 and the number of whitelisted reporters
+    InvalidQuorum = 5,
+
+    /// Reporter already submitted a price for this round
+    AlreadySubmitted = 6,
+
+    /// A price has already been recorded for this round
+    RoundFinalized = 7,
+
+    /// Timestamp is older than the most recent record
+    TimestampOutOfOrder = 8,
+
+    /// Retention must keep at least one record
+    InvalidRetention = 9,
+
+    /// Timestamp is not a multiple of the feed's resolution
+    TimestampNotAligned = 10,
+
+    /// Timestamp is more than one tick ahead of the current ledger time
+    TimestampInFuture = 11,
+
+    /// Price moved further from the previous record than the asset's maximum deviation
+    PriceDeviationExceeded = 12,
+
+    /// No longer returned: a gap past the asset's maximum is reported with a
+    /// `PriceGapDetected` event instead of rejecting the write. The code stays reserved so
+    /// clients decoding it keep working.
+    PriceGapExceeded = 13,
+
+    /// Asset has been deprecated and is no longer updated
+    AssetDeprecated = 14,
+
+    /// No admin transfer has been proposed
+    NoPendingAdmin = 15,
+
+    /// Key is not a registered price signer
+    NotSigner = 16,
+
+    /// Key is already a registered price signer
+    SignerAlreadyExists = 17,
+
+    /// Asset is derived from other assets and has no prices of its own
+    DerivedAsset = 18,
+
+    /// Formula is empty or reads an asset that isn't quoted or is itself derived
+    InvalidFormula = 19,
+
+    /// Confidence must not be negative
+    InvalidConfidence = 20,
+
+    /// Reporter staking is not enabled
+    StakingDisabled = 21,
+
+    /// Bond is below the minimum
+    InsufficientBond = 22,
+
+    /// Reporter has no bond
+    NotBonded = 23,
+
+    /// Staking terms are invalid
+    InvalidStakingConfig = 24,
+
+    /// Amount must be positive
+    InvalidAmount = 25,
+
+    /// Records within the retention window can't be pruned
+    PruneWithinRetention = 26,
+
+    /// Reporter has already started unbonding
+    AlreadyUnbonding = 27,
+
+    /// Reporter hasn't started unbonding
+    NotUnbonding = 28,
+
+    /// Bond is still within its unbonding period
+    BondLocked = 29,
+
+    /// Asset is read by a derived asset's formula
+    AssetInUse = 30,
+
+    /// Reporter was removed by the admin and can't rejoin by bonding
+    ReporterBanned = 31,
 }
 
 #[contracttype]
@@ -27,6 +132,7 @@ pub struct DataFeedStorage {
     base: Asset,
     decimals: u32,
     resolution: u32,
+    // unused: writes are ordered per asset, by the asset's own most recent record
     last_timestamp: u64,
 }
 
@@ -39,18 +145,89 @@ impl DataFeedStorage {
     pub fn set_state(env: &Env, storage: &DataFeedStorage) {
         env.storage().instance().set(&STORAGE, &storage);
     }
+
+    /// Round `timestamp` down to the start of its tick
+    fn snap(&self, timestamp: u64) -> u64 {
+        match self.resolution {
+            0 => timestamp,
+            resolution => timestamp - timestamp % resolution as u64,
+        }
+    }
+
+    /// Check that a price may be written for `timestamp`: it must be tick-aligned, no older
+    /// than the asset's `last` record, and at most `MAX_FUTURE_TICKS` ahead of the ledger time.
+    /// Other assets' records don't matter, so an asset that lags behind can still catch up.
+    fn check_timestamp(&self, env: &Env, timestamp: u64, last: Option<&PriceData>) {
+        if self.snap(timestamp) != timestamp {
+            panic_with_error!(env, Error::TimestampNotAligned);
+        }
+        if matches!(last, Some(last) if timestamp < last.timestamp) {
+            panic_with_error!(env, Error::TimestampOutOfOrder);
+        }
+        let max_timestamp = env
+            .ledger()
+            .timestamp()
+            .saturating_add(MAX_FUTURE_TICKS * self.resolution as u64);
+        if timestamp > max_timestamp {
+            panic_with_error!(env, Error::TimestampInFuture);
+        }
+    }
 }
 
 const ADMIN_KEY: Symbol = symbol_short!("ADMIN");
+const PENDING_ADMIN_KEY: Symbol = symbol_short!("PENDADMIN");
 const STORAGE: Symbol = symbol_short!("STORAGE");
+const REPORTERS: Symbol = symbol_short!("REPORTERS");
+const QUORUM: Symbol = symbol_short!("QUORUM");
+const RETENTION: Symbol = symbol_short!("RETENTION");
+const DEPRECATED: Symbol = symbol_short!("DEPRECATD");
+const SIGNERS: Symbol = symbol_short!("SIGNERS");
+const DELAY: Symbol = symbol_short!("DELAY");
+const BANNED: Symbol = symbol_short!("BANNED");
+
+/// Number of records kept per asset unless configured otherwise
+const DEFAULT_RETENTION: u32 = 1_000;
+
+/// How many ticks past the current ledger time a price may be timestamped
+const MAX_FUTURE_TICKS: u64 = 1;
+
+/// Number of ledgers an open round is kept alive for (~1 hour)
+const ROUND_TTL: u32 = 720;
+
+/// Smallest quorum allowed while staking is enabled, so no single bonded reporter can
+/// finalize a round alone
+const MIN_STAKED_QUORUM: u32 = 2;
+
+const DAY_IN_LEDGERS: u32 = 17_280;
+
+/// Price history entries are kept alive for ~30 days after each write or bump, and extended
+/// once they have less than ~29 days left
+pub(crate) const PERSISTENT_TTL: u32 = 30 * DAY_IN_LEDGERS;
+pub(crate) const PERSISTENT_TTL_THRESHOLD: u32 = PERSISTENT_TTL - DAY_IN_LEDGERS;
+
+/// Contract instance and code are kept alive for ~30 days after each write or bump
+const INSTANCE_TTL: u32 = 30 * DAY_IN_LEDGERS;
+const INSTANCE_TTL_THRESHOLD: u32 = INSTANCE_TTL - DAY_IN_LEDGERS;
 
 #[contracttype]
-enum DataKey {
+pub(crate) enum DataKey {
+    /// Legacy price history, a single Map<timestamp, price> per asset, holding the records not
+    /// yet moved by `migrate_prices`
     Prices(Asset),
-}
-
-fn new_asset_prices_map(env: &Env) -> Map<u64, i128> {
-    Map::new(env)
+    /// Submissions for an open round, keyed by (asset, timestamp)
+    Round(Asset, u64),
+    /// Bounds of an asset's price history
+    History(Asset),
+    /// A single price record, keyed by (asset, index)
+    Record(Asset, u64),
+    /// Circuit breaker limits for an asset
+    Guard(Asset),
+    /// Formula of a derived asset
+    Formula(Asset),
+    /// Confidence of the most recent price recorded with one
+    Confidence(Asset),
+    /// Tokens bonded by a reporter
+    Bond(Address),
 }
 
 #[contract]
@@ -82,22 +259,61 @@ impl DataFeed {
             last_timestamp: 0,
         };
         DataFeedStorage::set_state(env, &feed);
-        let new_map: Map<u64, i128> = Map::new(env);
+        env.storage()
+            .instance()
+            .set(&REPORTERS, &Vec::<Address>::new(env));
+        env.storage().instance().set(&QUORUM, &1u32);
+        env.storage().instance().set(&RETENTION, &DEFAULT_RETENTION);
         for asset in assets.into_iter() {
-            env.storage()
-                .persistent()
-                .set(&DataKey::Prices(asset), &new_map);
+            PriceHistory::default().set(env, &asset);
         }
+        Self::extend_instance_ttl(env);
         Ok(())
     }
 
     fn require_admin(env: &Env) {
-        let admin: Address = env
-            .storage()
+        Self::admin(env).require_auth();
+    }
+
+    /// Get the admin address
+    pub fn admin(env: &Env) -> Address {
+        env.storage()
             .instance()
             .get(&ADMIN_KEY)
-            .expect("Admin must be set");
-        admin.require_auth();
+            .expect("Admin must be set")
+    }
+
+    /// Get the address proposed as the next admin, if any
+    pub fn pending_admin(env: &Env) -> Option<Address> {
+        env.storage().instance().get(&PENDING_ADMIN_KEY)
+    }
+
+    /// Propose a new admin, who must call `accept_admin` to take over. Replaces any earlier
+    /// proposal. Admin-only.
+    pub fn propose_admin(env: &Env, new_admin: Address) {
+        Self::require_admin(env);
+        env.storage().instance().set(&PENDING_ADMIN_KEY, &new_admin);
+        AdminProposed {
+            admin: Self::admin(env),
+            new_admin,
+        }
+        .publish(env);
+    }
+
+    /// Complete an admin transfer. Must be signed by the proposed admin.
+    pub fn accept_admin(env: &Env) {
+        let Some(new_admin) = Self::pending_admin(env) else {
+            panic_with_error!(env, Error::NoPendingAdmin);
+        };
+        new_admin.require_auth();
+        let previous_admin = Self::admin(env);
+        env.storage().instance().set(&ADMIN_KEY, &new_admin);
+        env.storage().instance().remove(&PENDING_ADMIN_KEY);
+        AdminChanged {
+            previous_admin,
+            new_admin,
+        }
+        .publish(env);
     }
 
     /// Upgrade the contract to new wasm
@@ -106,18 +322,282 @@ impl DataFeed {
         env.deployer().update_current_contract_wasm(new_wasm_hash);
     }
 
-    fn get_asset_price(env: &Env, asset_id: Asset) -> Option<Map<u64, i128>> {
-        env.storage().persistent().get(&DataKey::Prices(asset_id))
+    /// Move up to `max_records` of an asset's legacy price records from the single-`Map` entry
+    /// of a feed deployed with the legacy layout into per-record entries, newest first, keeping
+    /// at most `retention` records in all. Until then the legacy records are still read as the
+    /// oldest part of the asset's history. Call repeatedly, resuming where the last call left
+    /// off, until it returns 0: the number of legacy records left to migrate. Admin-only.
+    pub fn migrate_prices(env: &Env, asset: Asset, max_records: u32) -> u32 {
+        Self::require_admin(env);
+        let mut history = Self::get_history(env, &asset);
+        history.migrate(env, &asset, max_records, Self::get_retention(env) as u64)
+    }
+
+    /// Extend the TTL of the contract instance and of an asset's configuration and most recent
+    /// `records` price records, so an idle feed's history isn't archived. Can be invoked by any
+    /// account.
+    pub fn bump(env: &Env, asset: Asset, records: u32) {
+        Self::extend_instance_ttl(env);
+        Self::get_history(env, &asset).extend_ttl(env, &asset, records);
+        for key in [DataKey::Guard(asset.clone()), DataKey::Formula(asset)] {
+            if env.storage().persistent().has(&key) {
+                env.storage().persistent().extend_ttl(
+                    &key,
+                    PERSISTENT_TTL_THRESHOLD,
+                    PERSISTENT_TTL,
+                );
+            }
+        }
+    }
+
+    fn extend_instance_ttl(env: &Env) {
+        env.storage()
+            .instance()
+            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL);
+    }
+
+    /// Number of records kept per asset
+    pub fn retention(env: &Env) -> u32 {
+        Self::get_retention(env)
+    }
+
+    fn get_retention(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&RETENTION)
+            .unwrap_or(DEFAULT_RETENTION)
+    }
+
+    /// Assets that have been deprecated and are no longer updated
+    pub fn deprecated_assets(env: &Env) -> Vec<Asset> {
+        Self::get_deprecated(env)
+    }
+
+    fn get_deprecated(env: &Env) -> Vec<Asset> {
+        env.storage()
+            .instance()
+            .get(&DEPRECATED)
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    /// Panic if a quoted or deprecated derived asset's formula reads `asset`, since its price
+    /// would stop evaluating once the component is gone
+    fn check_not_component(
+        env: &Env,
+        state: &DataFeedStorage,
+        deprecated: &Vec<Asset>,
+        asset: &Asset,
+    ) {
+        for other in state.assets.iter().chain(deprecated.iter()) {
+            if let Some(formula) = Formula::get(env, &other)
+                && formula.components(env).contains(asset)
+            {
+                panic_with_error!(env, Error::AssetInUse);
+            }
+        }
     }
 
-    fn set_asset_price_internal(env: &Env, asset_id: Asset, price: i128, timestamp: u64) {
-        let mut asset = Self::get_asset_price(env, asset_id.clone()).unwrap_or_else(|| {
+    /// Get the history of a quoted or deprecated asset
+    fn get_history(env: &Env, asset: &Asset) -> PriceHistory {
+        if !DataFeedStorage::get_state(env).assets.contains(asset)
+            && !Self::get_deprecated(env).contains(asset)
+        {
             panic_with_error!(env, Error::AssetNotFound);
-        });
-        asset.set(timestamp, price);
+        }
+        PriceHistory::load(env, asset)
+    }
+
+    /// Get the history of an asset that is still being updated
+    fn get_live_history(env: &Env, asset: &Asset) -> PriceHistory {
+        if Self::get_deprecated(env).contains(asset) {
+            panic_with_error!(env, Error::AssetDeprecated);
+        }
+        Self::get_history(env, asset)
+    }
+
+    /// Get the history of an asset that prices can be written to
+    fn get_writable_history(env: &Env, asset: &Asset) -> PriceHistory {
+        let history = Self::get_live_history(env, asset);
+        if Formula::get(env, asset).is_some() {
+            panic_with_error!(env, Error::DerivedAsset);
+        }
+        history
+    }
+
+    /// The most recent price of an asset at or before `timestamp`, evaluating the formula
+    /// of a derived asset
+    fn price_at(env: &Env, asset: &Asset, timestamp: u64) -> Option<PriceData> {
+        match Formula::get(env, asset) {
+            Some(formula) => formula
+                .evaluate(env, Self::decimals(env), timestamp)
+                .map(|(record, _)| record),
+            None => PriceHistory::load(env, asset).at_or_before(env, asset, timestamp),
+        }
+    }
+
+    /// Circuit breaker limits for an asset
+    pub fn price_guard(env: &Env, asset: Asset) -> PriceGuard {
+        PriceGuard::get(env, &asset)
+    }
+
+    fn set_asset_price_internal(
+        env: &Env,
+        asset_id: Asset,
+        price: i128,
+        timestamp: u64,
+        force: bool,
+    ) {
+        let mut history = Self::get_writable_history(env, &asset_id);
+        let last = history.last(env, &asset_id);
+        DataFeedStorage::get_state(env).check_timestamp(env, timestamp, last.as_ref());
+        let record = PriceData { price, timestamp };
+        if !force {
+            let guard = PriceGuard::get(env, &asset_id);
+            if let Some(previous_timestamp) = guard.check(env, last, &record) {
+                PriceGapDetected {
+                    asset: asset_id.clone(),
+                    previous_timestamp,
+                    timestamp,
+                }
+                .publish(env);
+            }
+        }
+        history.push(env, &asset_id, &record, Self::get_retention(env) as u64);
+        Self::extend_instance_ttl(env);
+    }
+
+    fn set_confidence(env: &Env, asset: Asset, record: &PriceConfidence) {
+        let key = DataKey::Confidence(asset);
+        env.storage().persistent().set(&key, record);
         env.storage()
             .persistent()
-            .set(&DataKey::Prices(asset_id), &asset);
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+    }
+
+    fn get_signers(env: &Env) -> Vec<BytesN<32>> {
+        env.storage()
+            .instance()
+            .get(&SIGNERS)
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    fn get_reporters(env: &Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&REPORTERS)
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    /// Reporters the admin removed, who may only be whitelisted again by the admin
+    fn get_banned(env: &Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&BANNED)
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    fn get_quorum(env: &Env) -> u32 {
+        env.storage().instance().get(&QUORUM).unwrap_or(1)
+    }
+
+    fn get_staking(env: &Env) -> StakingConfig {
+        StakingConfig::get(env).unwrap_or_else(|| panic_with_error!(env, Error::StakingDisabled))
+    }
+
+    /// Slash bonded reporters whose submission to a finalized round is beyond the tolerance
+    /// from the median, including those that have since left the whitelist, and reward the
+    /// whitelisted rest
+    fn settle_round(env: &Env, asset: &Asset, submissions: &Map<Address, i128>, median: i128) {
+        let Some(config) = StakingConfig::get(env) else {
+            return;
+        };
+        let mut pool = staking::reward_pool(env);
+        let mut reporters = Self::get_reporters(env);
+        for (reporter, price) in submissions.iter() {
+            let Some(mut bond) = Bond::get(env, &reporter) else {
+                continue;
+            };
+            let index = reporters.first_index_of(&reporter);
+            if config.accepts(price, median) {
+                if index.is_none() {
+                    continue;
+                }
+                let reward = config.reward.min(pool);
+                pool -= reward;
+                bond.rewards += reward;
+            } else {
+                let amount = config.slash_amount(bond.amount);
+                bond.amount -= amount;
+                pool += amount;
+                if let Some(index) = index
+                    && bond.amount < config.min_bond
+                {
+                    reporters.remove(index);
+                }
+                ReporterSlashed {
+                    reporter: reporter.clone(),
+                    asset: asset.clone(),
+                    price,
+                    median,
+                    amount,
+                }
+                .publish(env);
+            }
+            bond.set(env, &reporter);
+        }
+        staking::set_reward_pool(env, pool);
+        env.storage().instance().set(&REPORTERS, &reporters);
+    }
+
+    /// Price of `base` quoted in `quote`, keeping the feed's decimals. The timestamp is the
+    /// older of the two records. `None` if the quote price is zero or the result overflows.
+    fn cross_price(base: &PriceData, quote: &PriceData, decimals: u32) -> Option<PriceData> {
+        if quote.price == 0 {
+            return None;
+        }
+        let price = base
+            .price
+            .checked_mul(10i128.checked_pow(decimals)?)?
+            .checked_div(quote.price)?;
+        Some(PriceData {
+            price,
+            timestamp: base.timestamp.min(quote.timestamp),
+        })
+    }
+
+    /// Time-weighted average of records given newest first. Each record is weighted by the time
+    /// until the next one, and the newest by one tick. `None` if there are no records or the
+    /// sum overflows.
+    fn time_weighted_average(records: &Vec<PriceData>, resolution: u32) -> Option<i128> {
+        let mut weighted_sum: i128 = 0;
+        let mut total_weight: i128 = 0;
+        let mut until = records.first()?.timestamp + (resolution as u64).max(1);
+        for record in records.iter() {
+            let weight = until.saturating_sub(record.timestamp) as i128;
+            weighted_sum = weighted_sum.checked_add(record.price.checked_mul(weight)?)?;
+            total_weight += weight;
+            until = record.timestamp;
+        }
+        weighted_sum.checked_div(total_weight)
+    }
+
+    /// Median of the given prices; the mean of the two middle values for an even count
+    fn median(env: &Env, prices: Vec<i128>) -> i128 {
+        let mut sorted: Vec<i128> = Vec::new(env);
+        for price in prices {
+            let position = sorted
+                .iter()
+                .position(|p| p > price)
+                .unwrap_or(sorted.len() as usize);
+            sorted.insert(position as u32, price);
+        }
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 1 {
+            sorted.get_unchecked(mid)
+        } else {
+            let (low, high) = (sorted.get_unchecked(mid - 1), sorted.get_unchecked(mid));
+            low / 2 + high / 2 + (low % 2 + high % 2) / 2
+        }
     }
 }
 
@@ -127,15 +607,23 @@ impl IsSep40Admin for DataFeed {
         Self::require_admin(env);
         let current_storage = DataFeedStorage::get_state(env);
         let mut assets_vec = current_storage.assets;
-        for asset in assets {
+        let mut deprecated = Self::get_deprecated(env);
+        for asset in assets.iter() {
             if assets_vec.contains(&asset) {
                 panic_with_error!(env, Error::AssetAlreadyExists);
             }
             assets_vec.push_back(asset.clone());
-            env.storage()
-                .persistent()
-                .set(&DataKey::Prices(asset), &new_asset_prices_map(env));
+            if let Some(index) = deprecated.first_index_of(&asset) {
+                deprecated.remove(index);
+            }
+            // A deprecated derived asset added back this way is reported from now on
+            Formula::remove(env, &asset);
+            // Assets removed without clearing their history pick it back up
+            if PriceHistory::get(env, &asset).is_none() {
+                PriceHistory::load(env, &asset).set(env, &asset);
+            }
         }
+        env.storage().instance().set(&DEPRECATED, &deprecated);
         DataFeedStorage::set_state(
             env,
             &DataFeedStorage {
@@ -143,11 +631,668 @@ impl IsSep40Admin for DataFeed {
                 ..current_storage
             },
         );
+        AssetsAdded { assets }.publish(env);
+    }
+
+    fn deprecate_asset(env: &Env, asset: Asset) {
+        Self::require_admin(env);
+        let mut state = DataFeedStorage::get_state(env);
+        let Some(index) = state.assets.first_index_of(&asset) else {
+            panic_with_error!(env, Error::AssetNotFound);
+        };
+        let mut deprecated = Self::get_deprecated(env);
+        Self::check_not_component(env, &state, &deprecated, &asset);
+        state.assets.remove(index);
+        DataFeedStorage::set_state(env, &state);
+        deprecated.push_back(asset.clone());
+        env.storage().instance().set(&DEPRECATED, &deprecated);
+        AssetDeprecated { asset }.publish(env);
+    }
+
+    fn remove_asset(env: &Env, asset: Asset, clear_history: bool) {
+        Self::require_admin(env);
+        let mut state = DataFeedStorage::get_state(env);
+        let mut deprecated = Self::get_deprecated(env);
+        Self::check_not_component(env, &state, &deprecated, &asset);
+        if let Some(index) = state.assets.first_index_of(&asset) {
+            state.assets.remove(index);
+            DataFeedStorage::set_state(env, &state);
+        } else if let Some(index) = deprecated.first_index_of(&asset) {
+            deprecated.remove(index);
+            env.storage().instance().set(&DEPRECATED, &deprecated);
+        } else {
+            panic_with_error!(env, Error::AssetNotFound);
+        }
+        Formula::remove(env, &asset);
+        if clear_history {
+            PriceHistory::clear(env, &asset);
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Guard(asset.clone()));
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Confidence(asset.clone()));
+        }
+        AssetRemoved {
+            asset,
+            history_cleared: clear_history,
+        }
+        .publish(env);
     }
 
     fn set_asset_price(env: &Env, asset_id: Asset, price: i128, timestamp: u64) {
         Self::require_admin(env);
-        Self::set_asset_price_internal(env, asset_id, price, timestamp);
+        Self::set_asset_price_internal(env, asset_id.clone(), price, timestamp, false);
+        PriceUpdated {
+            asset: asset_id,
+            price,
+            timestamp,
+            reporter: Self::admin(env),
+        }
+        .publish(env);
+    }
+
+    fn set_asset_prices(env: &Env, prices: Vec<(Asset, i128)>, timestamp: u64) {
+        Self::require_admin(env);
+        for (asset, _) in prices.iter() {
+            Self::get_writable_history(env, &asset);
+        }
+        for (asset, price) in prices.iter() {
+            Self::set_asset_price_internal(env, asset, price, timestamp, false);
+        }
+        PricesUpdated {
+            timestamp,
+            prices,
+            reporter: Self::admin(env),
+        }
+        .publish(env);
+    }
+
+    fn set_price_guard(env: &Env, asset: Asset, max_deviation: u32, max_gap: u64) {
+        Self::require_admin(env);
+        Self::get_history(env, &asset);
+        PriceGuard {
+            max_deviation,
+            max_gap,
+        }
+        .set(env, &asset);
+        PriceGuardSet {
+            asset,
+            max_deviation,
+            max_gap,
+        }
+        .publish(env);
+    }
+
+    fn force_set_asset_price(env: &Env, asset: Asset, price: i128, timestamp: u64) {
+        Self::require_admin(env);
+        Self::set_asset_price_internal(env, asset.clone(), price, timestamp, true);
+        PriceForced {
+            asset,
+            price,
+            timestamp,
+            reporter: Self::admin(env),
+        }
+        .publish(env);
+    }
+
+    fn set_retention(env: &Env, retention: u32) {
+        Self::require_admin(env);
+        if retention == 0 {
+            panic_with_error!(env, Error::InvalidRetention);
+        }
+        env.storage().instance().set(&RETENTION, &retention);
+        RetentionSet { retention }.publish(env);
+    }
+
+    fn prune(env: &Env, asset: Asset, before_timestamp: u64) -> u32 {
+        Self::require_admin(env);
+        let mut history = Self::get_history(env, &asset);
+        // The window spans `retention` ticks back from the most recent record
+        if let Some(last) = history.last(env, &asset) {
+            let window = Self::get_retention(env) as u64 * Self::resolution(env) as u64;
+            if before_timestamp > last.timestamp.saturating_sub(window) {
+                panic_with_error!(env, Error::PruneWithinRetention);
+            }
+        }
+        let removed = history.prune(env, &asset, before_timestamp);
+        HistoryPruned {
+            asset,
+            before_timestamp,
+            removed,
+        }
+        .publish(env);
+        removed
+    }
+}
+
+#[contractimpl]
+impl IsMedianFeed for DataFeed {
+    fn add_reporters(env: &Env, reporters: Vec<Address>) {
+        Self::require_admin(env);
+        let mut current = Self::get_reporters(env);
+        let mut banned = Self::get_banned(env);
+        for reporter in reporters.iter() {
+            if current.contains(&reporter) {
+                panic_with_error!(env, Error::ReporterAlreadyExists);
+            }
+            if let Some(index) = banned.first_index_of(&reporter) {
+                banned.remove(index);
+            }
+            current.push_back(reporter);
+        }
+        env.storage().instance().set(&REPORTERS, &current);
+        env.storage().instance().set(&BANNED, &banned);
+        ReportersAdded { reporters }.publish(env);
+    }
+
+    fn remove_reporters(env: &Env, reporters: Vec<Address>) {
+        Self::require_admin(env);
+        let mut current = Self::get_reporters(env);
+        let mut banned = Self::get_banned(env);
+        for reporter in reporters.iter() {
+            let Some(index) = current.first_index_of(&reporter) else {
+                panic_with_error!(env, Error::NotReporter);
+            };
+            current.remove(index);
+            banned.push_back(reporter);
+        }
+        if current.len() < Self::get_quorum(env) {
+            panic_with_error!(env, Error::InvalidQuorum);
+        }
+        env.storage().instance().set(&REPORTERS, &current);
+        env.storage().instance().set(&BANNED, &banned);
+        ReportersRemoved { reporters }.publish(env);
+    }
+
+    fn set_quorum(env: &Env, quorum: u32) {
+        Self::require_admin(env);
+        if quorum == 0 || quorum > Self::get_reporters(env).len() {
+            panic_with_error!(env, Error::InvalidQuorum);
+        }
+        if quorum < MIN_STAKED_QUORUM && StakingConfig::get(env).is_some() {
+            panic_with_error!(env, Error::InvalidQuorum);
+        }
+        env.storage().instance().set(&QUORUM, &quorum);
+        QuorumSet { quorum }.publish(env);
+    }
+
+    fn reporters(env: &Env) -> Vec<Address> {
+        Self::get_reporters(env)
+    }
+
+    fn quorum(env: &Env) -> u32 {
+        Self::get_quorum(env)
+    }
+
+    fn submit_price(env: &Env, reporter: Address, asset: Asset, price: i128, timestamp: u64) {
+        reporter.require_auth();
+        let reporters = Self::get_reporters(env);
+        if !reporters.contains(&reporter) {
+            panic_with_error!(env, Error::NotReporter);
+        }
+        let history = Self::get_writable_history(env, &asset);
+        let last = history.last(env, &asset);
+        if matches!(&last, Some(last) if last.timestamp == timestamp) {
+            panic_with_error!(env, Error::RoundFinalized);
+        }
+        DataFeedStorage::get_state(env).check_timestamp(env, timestamp, last.as_ref());
+
+        let key = DataKey::Round(asset.clone(), timestamp);
+        let mut submissions: Map<Address, i128> = env
+            .storage()
+            .temporary()
+            .get(&key)
+            .unwrap_or_else(|| Map::new(env));
+        if submissions.contains_key(reporter.clone()) {
+            panic_with_error!(env, Error::AlreadySubmitted);
+        }
+        submissions.set(reporter.clone(), price);
+        PriceSubmitted {
+            asset: asset.clone(),
+            price,
+            timestamp,
+            reporter,
+        }
+        .publish(env);
+
+        // Only count submissions from reporters that are still whitelisted
+        let mut prices: Vec<i128> = Vec::new(env);
+        let mut contributors: Vec<Address> = Vec::new(env);
+        for (submitter, submitted) in submissions.iter() {
+            if reporters.contains(&submitter) {
+                prices.push_back(submitted);
+                contributors.push_back(submitter);
+            }
+        }
+
+        if prices.len() >= Self::get_quorum(env) {
+            env.storage().temporary().remove(&key);
+            // The interval spans every counted submission
+            let mut low = i128::MAX;
+            let mut high = i128::MIN;
+            for price in prices.iter() {
+                low = low.min(price);
+                high = high.max(price);
+            }
+            let median = Self::median(env, prices);
+            Self::set_asset_price_internal(env, asset.clone(), median, timestamp, false);
+            Self::settle_round(env, &asset, &submissions, median);
+            Self::set_confidence(
+                env,
+                asset.clone(),
+                &PriceConfidence {
+                    price: median,
+                    confidence: (high - median).max(median - low),
+                    timestamp,
+                },
+            );
+            PriceAggregated {
+                asset,
+                price: median,
+                timestamp,
+                reporters: contributors,
+            }
+            .publish(env);
+        } else {
+            env.storage().temporary().set(&key, &submissions);
+            env.storage()
+                .temporary()
+                .extend_ttl(&key, ROUND_TTL, ROUND_TTL);
+        }
+    }
+}
+
+#[contractimpl]
+impl IsStakedReporters for DataFeed {
+    fn set_staking(env: &Env, config: StakingConfig) {
+        Self::require_admin(env);
+        if config.min_bond <= 0
+            || config.reward < 0
+            || config.slash > 10_000
+            || config.unbonding_period < ROUND_TTL
+        {
+            panic_with_error!(env, Error::InvalidStakingConfig);
+        }
+        // Existing bonds must stay redeemable in the token they were made in
+        if matches!(StakingConfig::get(env), Some(current) if current.token != config.token) {
+            panic_with_error!(env, Error::InvalidStakingConfig);
+        }
+        if Self::get_quorum(env) < MIN_STAKED_QUORUM {
+            panic_with_error!(env, Error::InvalidQuorum);
+        }
+        config.set(env);
+    }
+
+    fn staking(env: &Env) -> Option<StakingConfig> {
+        StakingConfig::get(env)
+    }
+
+    fn bond(env: &Env, reporter: Address, amount: i128) {
+        reporter.require_auth();
+        let config = Self::get_staking(env);
+        let mut reporters = Self::get_reporters(env);
+        let mut bond = Bond::get(env, &reporter).unwrap_or_default();
+        if amount <= 0 {
+            panic_with_error!(env, Error::InvalidAmount);
+        }
+        if Self::get_banned(env).contains(&reporter) {
+            panic_with_error!(env, Error::ReporterBanned);
+        }
+        bond.amount += amount;
+        bond.unlocks_at = 0;
+        if bond.amount < config.min_bond {
+            panic_with_error!(env, Error::InsufficientBond);
+        }
+        TokenClient::new(env, &config.token).transfer(
+            &reporter,
+            env.current_contract_address(),
+            &amount,
+        );
+        bond.set(env, &reporter);
+        // Also brings back reporters removed after being slashed below the minimum or unbonding,
+        // but not those the admin removed
+        if !reporters.contains(&reporter) {
+            reporters.push_back(reporter.clone());
+            env.storage().instance().set(&REPORTERS, &reporters);
+        }
+        ReporterBonded {
+            reporter,
+            amount: bond.amount,
+        }
+        .publish(env);
+    }
+
+    fn unbond(env: &Env, reporter: Address) {
+        reporter.require_auth();
+        let config = Self::get_staking(env);
+        let Some(mut bond) = Bond::get(env, &reporter) else {
+            panic_with_error!(env, Error::NotBonded);
+        };
+        if bond.unlocks_at != 0 {
+            panic_with_error!(env, Error::AlreadyUnbonding);
+        }
+        let mut reporters = Self::get_reporters(env);
+        if let Some(index) = reporters.first_index_of(&reporter) {
+            reporters.remove(index);
+            if reporters.len() < Self::get_quorum(env) {
+                panic_with_error!(env, Error::InvalidQuorum);
+            }
+            env.storage().instance().set(&REPORTERS, &reporters);
+        }
+        bond.unlocks_at = env.ledger().sequence() + config.unbonding_period;
+        bond.set(env, &reporter);
+        ReporterUnbonding {
+            reporter,
+            unlocks_at: bond.unlocks_at,
+        }
+        .publish(env);
+    }
+
+    fn withdraw_bond(env: &Env, reporter: Address) {
+        reporter.require_auth();
+        let Some(bond) = Bond::get(env, &reporter) else {
+            panic_with_error!(env, Error::NotBonded);
+        };
+        if bond.unlocks_at == 0 {
+            panic_with_error!(env, Error::NotUnbonding);
+        }
+        if env.ledger().sequence() < bond.unlocks_at {
+            panic_with_error!(env, Error::BondLocked);
+        }
+        Bond::remove(env, &reporter);
+        let amount = bond.amount + bond.rewards;
+        TokenClient::new(env, &Self::get_staking(env).token).transfer(
+            &env.current_contract_address(),
+            &reporter,
+            &amount,
+        );
+        ReporterUnbonded { reporter, amount }.publish(env);
+    }
+
+    fn bond_of(env: &Env, reporter: Address) -> Option<Bond> {
+        Bond::get(env, &reporter)
+    }
+
+    fn claim_rewards(env: &Env, reporter: Address) -> i128 {
+        reporter.require_auth();
+        let config = Self::get_staking(env);
+        let Some(mut bond) = Bond::get(env, &reporter) else {
+            panic_with_error!(env, Error::NotBonded);
+        };
+        let rewards = bond.rewards;
+        bond.rewards = 0;
+        bond.set(env, &reporter);
+        if rewards > 0 {
+            TokenClient::new(env, &config.token).transfer(
+                &env.current_contract_address(),
+                &reporter,
+                &rewards,
+            );
+        }
+        rewards
+    }
+
+    fn fund_rewards(env: &Env, from: Address, amount: i128) {
+        from.require_auth();
+        let config = Self::get_staking(env);
+        if amount <= 0 {
+            panic_with_error!(env, Error::InvalidAmount);
+        }
+        TokenClient::new(env, &config.token).transfer(
+            &from,
+            env.current_contract_address(),
+            &amount,
+        );
+        staking::set_reward_pool(env, staking::reward_pool(env) + amount);
+    }
+
+    fn reward_pool(env: &Env) -> i128 {
+        staking::reward_pool(env)
+    }
+}
+
+#[contractimpl]
+impl IsSignedFeed for DataFeed {
+    fn add_signers(env: &Env, signers: Vec<BytesN<32>>) {
+        Self::require_admin(env);
+        let mut current = Self::get_signers(env);
+        for signer in signers.iter() {
+            if current.contains(&signer) {
+                panic_with_error!(env, Error::SignerAlreadyExists);
+            }
+            current.push_back(signer);
+        }
+        env.storage().instance().set(&SIGNERS, &current);
+        SignersAdded { signers }.publish(env);
+    }
+
+    fn remove_signers(env: &Env, signers: Vec<BytesN<32>>) {
+        Self::require_admin(env);
+        let mut current = Self::get_signers(env);
+        for signer in signers.iter() {
+            let Some(index) = current.first_index_of(&signer) else {
+                panic_with_error!(env, Error::NotSigner);
+            };
+            current.remove(index);
+        }
+        env.storage().instance().set(&SIGNERS, &current);
+        SignersRemoved { signers }.publish(env);
+    }
+
+    fn signers(env: &Env) -> Vec<BytesN<32>> {
+        Self::get_signers(env)
+    }
+
+    fn submit_signed_price(
+        env: &Env,
+        signer: BytesN<32>,
+        asset: Asset,
+        price: i128,
+        timestamp: u64,
+        signature: BytesN<64>,
+    ) {
+        if !Self::get_signers(env).contains(&signer) {
+            panic_with_error!(env, Error::NotSigner);
+        }
+        let message = (
+            env.current_contract_address(),
+            asset.clone(),
+            price,
+            timestamp,
+        )
+            .to_xdr(env);
+        env.crypto().ed25519_verify(&signer, &message, &signature);
+
+        let history = Self::get_writable_history(env, &asset);
+        if matches!(history.last(env, &asset), Some(last) if last.timestamp == timestamp) {
+            panic_with_error!(env, Error::RoundFinalized);
+        }
+        Self::set_asset_price_internal(env, asset.clone(), price, timestamp, false);
+        SignedPriceUpdated {
+            asset,
+            price,
+            timestamp,
+            signer,
+        }
+        .publish(env);
+    }
+}
+
+#[contractimpl]
+impl IsConfidenceFeed for DataFeed {
+    fn lastprice_confidence(env: &Env, asset: Asset) -> Option<PriceConfidence> {
+        let last = Self::lastprice(env, asset.clone())?;
+        let stored: Option<PriceConfidence> =
+            env.storage().persistent().get(&DataKey::Confidence(asset));
+        match stored {
+            // Only applies if the most recent record hasn't been overwritten since
+            Some(record) if record.timestamp == last.timestamp && record.price == last.price => {
+                Some(record)
+            }
+            _ => Some(PriceConfidence {
+                price: last.price,
+                confidence: 0,
+                timestamp: last.timestamp,
+            }),
+        }
+    }
+
+    fn set_asset_price_with_confidence(
+        env: &Env,
+        asset: Asset,
+        price: i128,
+        confidence: i128,
+        timestamp: u64,
+    ) {
+        Self::require_admin(env);
+        if confidence < 0 {
+            panic_with_error!(env, Error::InvalidConfidence);
+        }
+        Self::set_asset_price_internal(env, asset.clone(), price, timestamp, false);
+        Self::set_confidence(
+            env,
+            asset.clone(),
+            &PriceConfidence {
+                price,
+                confidence,
+                timestamp,
+            },
+        );
+        PriceUpdated {
+            asset,
+            price,
+            timestamp,
+            reporter: Self::admin(env),
+        }
+        .publish(env);
+    }
+}
+
+#[contractimpl]
+impl IsDelayedFeed for DataFeed {
+    fn price_delay(env: &Env) -> u64 {
+        env.storage().instance().get(&DELAY).unwrap_or(0)
+    }
+
+    fn set_price_delay(env: &Env, delay: u64) {
+        Self::require_admin(env);
+        env.storage().instance().set(&DELAY, &delay);
+        PriceDelaySet { delay }.publish(env);
+    }
+
+    fn current_price(env: &Env, asset: Asset) -> Option<PriceData> {
+        Self::get_live_history(env, &asset);
+        let cutoff = env
+            .ledger()
+            .timestamp()
+            .saturating_sub(Self::price_delay(env));
+        Self::price_at(env, &asset, cutoff)
+    }
+
+    fn next_price(env: &Env, asset: Asset) -> Option<PriceData> {
+        Self::lastprice(env, asset)
+    }
+}
+
+#[contractimpl]
+impl IsDerivedFeed for DataFeed {
+    fn add_derived_asset(env: &Env, asset: Asset, formula: Formula) {
+        Self::require_admin(env);
+        let mut state = DataFeedStorage::get_state(env);
+        if state.assets.contains(&asset) {
+            panic_with_error!(env, Error::AssetAlreadyExists);
+        }
+        let deprecated = Self::get_deprecated(env);
+        if deprecated.contains(&asset) {
+            panic_with_error!(env, Error::AssetDeprecated);
+        }
+        let components = formula.components(env);
+        if components.is_empty() {
+            panic_with_error!(env, Error::InvalidFormula);
+        }
+        for component in components.iter() {
+            if deprecated.contains(&component) {
+                panic_with_error!(env, Error::AssetDeprecated);
+            }
+            if !state.assets.contains(&component) || Formula::get(env, &component).is_some() {
+                panic_with_error!(env, Error::InvalidFormula);
+            }
+        }
+        state.assets.push_back(asset.clone());
+        DataFeedStorage::set_state(env, &state);
+        formula.set(env, &asset);
+        DerivedAssetAdded { asset, formula }.publish(env);
+    }
+
+    fn formula(env: &Env, asset: Asset) -> Option<Formula> {
+        Formula::get(env, &asset)
+    }
+}
+
+#[contractimpl]
+impl IsSep40Cross for DataFeed {
+    fn x_last_price(env: &Env, base_asset: Asset, quote_asset: Asset) -> Option<PriceData> {
+        let base = Self::lastprice(env, base_asset)?;
+        let quote = Self::lastprice(env, quote_asset)?;
+        Self::cross_price(&base, &quote, Self::decimals(env))
+    }
+
+    fn x_price(
+        env: &Env,
+        base_asset: Asset,
+        quote_asset: Asset,
+        timestamp: u64,
+    ) -> Option<PriceData> {
+        let base = Self::price(env, base_asset, timestamp)?;
+        let quote = Self::price(env, quote_asset, timestamp)?;
+        Self::cross_price(&base, &quote, Self::decimals(env))
+    }
+
+    fn x_prices(
+        env: &Env,
+        base_asset: Asset,
+        quote_asset: Asset,
+        records: u32,
+    ) -> Option<Vec<PriceData>> {
+        let decimals = Self::decimals(env);
+        Self::get_history(env, &quote_asset);
+        let mut prices = Vec::new(env);
+        for base in Self::prices(env, base_asset, records)? {
+            let Some(quote) = Self::price_at(env, &quote_asset, base.timestamp) else {
+                break;
+            };
+            prices.push_back(Self::cross_price(&base, &quote, decimals)?);
+        }
+        Some(prices)
+    }
+}
+
+#[contractimpl]
+impl IsSep40Twap for DataFeed {
+    fn twap(env: &Env, asset: Asset, records: u32) -> Option<i128> {
+        Self::get_live_history(env, &asset);
+        let prices = Self::prices(env, asset, records)?;
+        Self::time_weighted_average(&prices, Self::resolution(env))
+    }
+
+    fn x_twap(env: &Env, base_asset: Asset, quote_asset: Asset, records: u32) -> Option<i128> {
+        let decimals = Self::decimals(env);
+        Self::get_live_history(env, &base_asset);
+        Self::get_live_history(env, &quote_asset);
+        let mut prices = Vec::new(env);
+        for base in Self::prices(env, base_asset, records)? {
+            let Some(quote) = Self::price_at(env, &quote_asset, base.timestamp) else {
+                break;
+            };
+            // Weight by the base record's timestamp so each base tick counts once
+            let cross = Self::cross_price(&base, &quote, decimals)?;
+            prices.push_back(PriceData {
+                price: cross.price,
+                timestamp: base.timestamp,
+            });
+        }
+        Self::time_weighted_average(&prices, Self::resolution(env))
     }
 }
 
@@ -166,39 +1311,25 @@ impl IsSep40 for DataFeed {
     }
 
     fn lastprice(env: &Env, asset: Asset) -> Option<PriceData> {
-        let Some(asset) = Self::get_asset_price(env, asset.clone()) else {
-            panic_with_error!(env, Error::AssetNotFound);
-        };
-        let timestamp = asset.keys().last()?;
-        let price = asset.get(timestamp)?;
-        Some(PriceData { price, timestamp })
+        let history = Self::get_live_history(env, &asset);
+        match Formula::get(env, &asset) {
+            Some(_) => Self::price_at(env, &asset, u64::MAX),
+            None => history.last(env, &asset),
+        }
     }
 
     fn price(env: &Env, asset: Asset, timestamp: u64) -> Option<PriceData> {
-        let Some(asset) = Self::get_asset_price(env, asset.clone()) else {
-            panic_with_error!(env, Error::AssetNotFound);
-        };
-        let price = asset.get(timestamp)?;
-        Some(PriceData { price, timestamp })
+        let timestamp = DataFeedStorage::get_state(env).snap(timestamp);
+        Self::get_history(env, &asset);
+        Self::price_at(env, &asset, timestamp)
     }
 
     fn prices(env: &Env, asset: Asset, records: u32) -> Option<Vec<PriceData>> {
-        let Some(asset) = Self::get_asset_price(env, asset.clone()) else {
-            panic_with_error!(env, Error::AssetNotFound);
-        };
-        let mut prices = vec![env];
-        asset
-            .keys()
-            .iter()
-            .rev()
-            .take(records as usize)
-            .for_each(|timestamp| {
-                prices.push_back(PriceData {
-                    price: asset.get_unchecked(timestamp),
-                    timestamp,
-                })
-            });
-        Some(prices)
+        let history = Self::get_history(env, &asset);
+        match Formula::get(env, &asset) {
+            Some(formula) => Some(formula.latest(env, Self::decimals(env), records)),
+            None => Some(history.latest(env, &asset, records)),
+        }
     }
 
     fn resolution(env: &Env) -> u32 {
@@ -0,0 +1,26 @@
+use crate::{Asset, PriceData};
+use soroban_sdk::Env;
+
+/// Interface for consumers that act on prices only after a delay, in the style of Maker's
+/// Oracle Security Module. A price becomes current once its timestamp is at least
+/// `price_delay` seconds old, which gives CDP owners a window to react to a new price before
+/// it can be used against them. With a delay of zero, current and next prices are the same.
+pub trait IsDelayedFeed {
+    /// Return the delay before a price becomes current, in seconds
+    fn price_delay(env: &Env) -> u64;
+
+    /// Set the delay before a price becomes current, in seconds. Can be invoked only by the
+    /// admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin address
+    fn set_price_delay(env: &Env, delay: u64);
+
+    /// Get the most recent price for an asset that is at least `price_delay` seconds old
+    fn current_price(env: &Env, asset: Asset) -> Option<PriceData>;
+
+    /// Get the most recent price for an asset, which becomes current once it is `price_delay`
+    /// seconds old
+    fn next_price(env: &Env, asset: Asset) -> Option<PriceData>;
+}
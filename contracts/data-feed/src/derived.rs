@@ -0,0 +1,19 @@
+use crate::Asset;
+use crate::formula::Formula;
+use soroban_sdk::Env;
+
+/// Interface for derived assets, whose prices are computed on read from other assets in the
+/// feed instead of being reported. `lastprice`, `price` and `prices` evaluate the formula.
+pub trait IsDerivedFeed {
+    /// Add an asset priced by `formula`. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, if the asset is already quoted, if the asset
+    /// or any asset the formula reads is deprecated, or if the formula is empty or reads an
+    /// asset that isn't quoted or is itself derived
+    fn add_derived_asset(env: &Env, asset: Asset, formula: Formula);
+
+    /// Return the formula of a derived asset, or `None` for a reported asset
+    fn formula(env: &Env, asset: Asset) -> Option<Formula>;
+}
@@ -0,0 +1,200 @@
+use soroban_sdk::{Address, BytesN, Vec, contractevent};
+
+use crate::Asset;
+use crate::formula::Formula;
+
+/// A price was recorded directly by `reporter`
+#[contractevent(topics = ["price_updated"])]
+pub struct PriceUpdated {
+    #[topic]
+    pub asset: Asset,
+    pub price: i128,
+    pub timestamp: u64,
+    pub reporter: Address,
+}
+
+/// A round reached quorum and its median was recorded. `reporters` are the whitelisted
+/// reporters whose submissions were counted.
+#[contractevent(topics = ["price_aggregated"])]
+pub struct PriceAggregated {
+    #[topic]
+    pub asset: Asset,
+    pub price: i128,
+    pub timestamp: u64,
+    pub reporters: Vec<Address>,
+}
+
+/// Prices for several assets were recorded under one timestamp
+#[contractevent(topics = ["prices_updated"])]
+pub struct PricesUpdated {
+    #[topic]
+    pub timestamp: u64,
+    pub prices: Vec<(Asset, i128)>,
+    pub reporter: Address,
+}
+
+/// A price was recorded after a gap longer than the asset's maximum, so it wasn't checked
+/// against the stale previous record
+#[contractevent(topics = ["price_gap_detected"])]
+pub struct PriceGapDetected {
+    #[topic]
+    pub asset: Asset,
+    pub previous_timestamp: u64,
+    pub timestamp: u64,
+}
+
+/// A price was recorded without applying the asset's circuit breaker limits
+#[contractevent(topics = ["price_forced"])]
+pub struct PriceForced {
+    #[topic]
+    pub asset: Asset,
+    pub price: i128,
+    pub timestamp: u64,
+    pub reporter: Address,
+}
+
+/// A price signed off-chain by a registered key was recorded
+#[contractevent(topics = ["signed_price_updated"])]
+pub struct SignedPriceUpdated {
+    #[topic]
+    pub asset: Asset,
+    pub price: i128,
+    pub timestamp: u64,
+    pub signer: BytesN<32>,
+}
+
+/// A reporter bonded tokens, `amount` being its bond afterwards
+#[contractevent(topics = ["reporter_bonded"], data_format = "single-value")]
+pub struct ReporterBonded {
+    #[topic]
+    pub reporter: Address,
+    pub amount: i128,
+}
+
+/// A reporter left the whitelist, and may withdraw its bond from ledger `unlocks_at`
+#[contractevent(topics = ["reporter_unbonding"], data_format = "single-value")]
+pub struct ReporterUnbonding {
+    #[topic]
+    pub reporter: Address,
+    pub unlocks_at: u32,
+}
+
+/// A reporter withdrew its bond and unclaimed rewards, `amount` in total
+#[contractevent(topics = ["reporter_unbonded"], data_format = "single-value")]
+pub struct ReporterUnbonded {
+    #[topic]
+    pub reporter: Address,
+    pub amount: i128,
+}
+
+/// A bonded reporter's submission was beyond the tolerance from the finalized median
+#[contractevent(topics = ["reporter_slashed"])]
+pub struct ReporterSlashed {
+    #[topic]
+    pub reporter: Address,
+    #[topic]
+    pub asset: Asset,
+    pub price: i128,
+    pub median: i128,
+    pub amount: i128,
+}
+
+/// A reporter submitted a price for a round
+#[contractevent(topics = ["price_submitted"])]
+pub struct PriceSubmitted {
+    #[topic]
+    pub asset: Asset,
+    pub price: i128,
+    pub timestamp: u64,
+    pub reporter: Address,
+}
+
+#[contractevent(topics = ["assets_added"], data_format = "single-value")]
+pub struct AssetsAdded {
+    pub assets: Vec<Asset>,
+}
+
+#[contractevent(topics = ["derived_asset_added"], data_format = "single-value")]
+pub struct DerivedAssetAdded {
+    #[topic]
+    pub asset: Asset,
+    pub formula: Formula,
+}
+
+#[contractevent(topics = ["asset_deprecated"], data_format = "vec")]
+pub struct AssetDeprecated {
+    #[topic]
+    pub asset: Asset,
+}
+
+#[contractevent(topics = ["asset_removed"], data_format = "single-value")]
+pub struct AssetRemoved {
+    #[topic]
+    pub asset: Asset,
+    pub history_cleared: bool,
+}
+
+#[contractevent(topics = ["history_pruned"])]
+pub struct HistoryPruned {
+    #[topic]
+    pub asset: Asset,
+    pub before_timestamp: u64,
+    pub removed: u32,
+}
+
+#[contractevent(topics = ["admin_proposed"], data_format = "single-value")]
+pub struct AdminProposed {
+    #[topic]
+    pub admin: Address,
+    pub new_admin: Address,
+}
+
+#[contractevent(topics = ["admin_changed"], data_format = "single-value")]
+pub struct AdminChanged {
+    #[topic]
+    pub previous_admin: Address,
+    pub new_admin: Address,
+}
+
+#[contractevent(topics = ["reporters_added"], data_format = "single-value")]
+pub struct ReportersAdded {
+    pub reporters: Vec<Address>,
+}
+
+#[contractevent(topics = ["reporters_removed"], data_format = "single-value")]
+pub struct ReportersRemoved {
+    pub reporters: Vec<Address>,
+}
+
+#[contractevent(topics = ["quorum_set"], data_format = "single-value")]
+pub struct QuorumSet {
+    pub quorum: u32,
+}
+
+#[contractevent(topics = ["retention_set"], data_format = "single-value")]
+pub struct RetentionSet {
+    pub retention: u32,
+}
+
+#[contractevent(topics = ["price_delay_set"], data_format = "single-value")]
+pub struct PriceDelaySet {
+    pub delay: u64,
+}
+
+#[contractevent(topics = ["price_guard_set"])]
+pub struct PriceGuardSet {
+    #[topic]
+    pub asset: Asset,
+    pub max_deviation: u32,
+    pub max_gap: u64,
+}
+
+#[contractevent(topics = ["signers_added"], data_format = "single-value")]
+pub struct SignersAdded {
+    pub signers: Vec<BytesN<32>>,
+}
+
+#[contractevent(topics = ["signers_removed"], data_format = "single-value")]
+pub struct SignersRemoved {
+    pub signers: Vec<BytesN<32>>,
+}
@@ -0,0 +1,112 @@
+use soroban_sdk::{Env, Vec, contracttype};
+
+use crate::data_feed::{DataKey, PERSISTENT_TTL, PERSISTENT_TTL_THRESHOLD};
+use crate::history::PriceHistory;
+use crate::{Asset, PriceData};
+
+/// How a derived asset's price is computed from the prices of other assets in the feed.
+/// Components must be assets with their own price records; formulas don't nest.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Formula {
+    /// Price of the first asset divided by the price of the second, e.g. EUR/XLM from
+    /// EUR/USD and XLM/USD
+    Cross(Asset, Asset),
+    /// Sum of component prices, each multiplied by a weight with the feed's decimals
+    Basket(Vec<(Asset, i128)>),
+}
+
+impl Formula {
+    /// Get the formula of a derived asset, if it is one
+    pub fn get(env: &Env, asset: &Asset) -> Option<Formula> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Formula(asset.clone()))
+    }
+
+    pub fn set(&self, env: &Env, asset: &Asset) {
+        let key = DataKey::Formula(asset.clone());
+        env.storage().persistent().set(&key, self);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+    }
+
+    pub fn remove(env: &Env, asset: &Asset) {
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Formula(asset.clone()));
+    }
+
+    /// Assets the formula reads
+    pub fn components(&self, env: &Env) -> Vec<Asset> {
+        match self {
+            Formula::Cross(base, quote) => Vec::from_array(env, [base.clone(), quote.clone()]),
+            Formula::Basket(weights) => {
+                let mut assets = Vec::new(env);
+                for (asset, _) in weights.iter() {
+                    assets.push_back(asset);
+                }
+                assets
+            }
+        }
+    }
+
+    /// Evaluate the formula from each component's most recent record at or before
+    /// `timestamp`. The result is timestamped with the oldest component record, since a
+    /// derived price is only as fresh as its stalest input. Also returns the newest component
+    /// timestamp, i.e. when the result took effect. `None` if a component has no record, the
+    /// divisor is zero, or the result overflows.
+    pub fn evaluate(&self, env: &Env, decimals: u32, timestamp: u64) -> Option<(PriceData, u64)> {
+        let mut records = Vec::new(env);
+        let (mut oldest, mut newest) = (u64::MAX, 0);
+        for asset in self.components(env) {
+            let record = PriceHistory::get(env, &asset)?.at_or_before(env, &asset, timestamp)?;
+            oldest = oldest.min(record.timestamp);
+            newest = newest.max(record.timestamp);
+            records.push_back(record.price);
+        }
+        let scale = 10i128.checked_pow(decimals)?;
+        let price = match self {
+            Formula::Cross(..) => {
+                let (base, quote) = (records.get_unchecked(0), records.get_unchecked(1));
+                if quote == 0 {
+                    return None;
+                }
+                base.checked_mul(scale)?.checked_div(quote)?
+            }
+            Formula::Basket(weights) => {
+                let mut sum: i128 = 0;
+                for ((_, weight), price) in weights.iter().zip(records.iter()) {
+                    sum = sum.checked_add(price.checked_mul(weight)?)?;
+                }
+                sum / scale
+            }
+        };
+        Some((
+            PriceData {
+                price,
+                timestamp: oldest,
+            },
+            newest,
+        ))
+    }
+
+    /// Up to `records` evaluations, newest first, one for each point at which a component
+    /// price changed
+    pub fn latest(&self, env: &Env, decimals: u32, records: u32) -> Vec<PriceData> {
+        let mut prices = Vec::new(env);
+        let mut timestamp = u64::MAX;
+        while prices.len() < records {
+            let Some((record, effective)) = self.evaluate(env, decimals, timestamp) else {
+                break;
+            };
+            prices.push_back(record);
+            if effective == 0 {
+                break;
+            }
+            timestamp = effective - 1;
+        }
+        prices
+    }
+}
@@ -0,0 +1,61 @@
+use soroban_sdk::{Env, contracttype, panic_with_error};
+
+use crate::data_feed::{DataKey, Error, PERSISTENT_TTL, PERSISTENT_TTL_THRESHOLD};
+use crate::{Asset, PriceData};
+
+const BASIS_POINTS: i128 = 10_000;
+
+/// Circuit breaker limits for updates to an asset's price. A limit of zero disables that check.
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PriceGuard {
+    /// Maximum change from the previous record, in basis points
+    pub max_deviation: u32,
+    /// Maximum time since the previous record, in seconds. Beyond it the previous price is
+    /// too stale to judge the update against, so the deviation check is skipped and the gap
+    /// reported instead. This lets the feed resume on its own after an outage.
+    pub max_gap: u64,
+}
+
+impl PriceGuard {
+    /// Get the limits configured for an asset; all checks are disabled if none are configured
+    pub fn get(env: &Env, asset: &Asset) -> PriceGuard {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Guard(asset.clone()))
+            .unwrap_or_default()
+    }
+
+    pub fn set(&self, env: &Env, asset: &Asset) {
+        let key = DataKey::Guard(asset.clone());
+        env.storage().persistent().set(&key, self);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+    }
+
+    /// Reject an update to `record` that moves further from `previous` than the maximum
+    /// deviation. Returns the timestamp of `previous` if it is older than the maximum gap, in
+    /// which case the update is accepted unchecked.
+    pub fn check(&self, env: &Env, previous: Option<PriceData>, record: &PriceData) -> Option<u64> {
+        let previous = previous?;
+        if self.max_gap > 0 && record.timestamp.saturating_sub(previous.timestamp) > self.max_gap {
+            return Some(previous.timestamp);
+        }
+        if self.max_deviation > 0 && !self.within_deviation(previous.price, record.price) {
+            panic_with_error!(env, Error::PriceDeviationExceeded);
+        }
+        None
+    }
+
+    fn within_deviation(&self, previous: i128, price: i128) -> bool {
+        if previous == 0 {
+            return price == 0;
+        }
+        let difference = price.abs_diff(previous);
+        let Some(scaled) = difference.checked_mul(BASIS_POINTS as u128) else {
+            return false;
+        };
+        scaled / previous.unsigned_abs() <= self.max_deviation as u128
+    }
+}
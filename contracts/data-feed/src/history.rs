@@ -0,0 +1,278 @@
+use soroban_sdk::{Env, Map, Vec, contracttype, panic_with_error, vec};
+
+use crate::data_feed::{DataKey, Error, PERSISTENT_TTL, PERSISTENT_TTL_THRESHOLD};
+use crate::{Asset, PriceData};
+
+/// Most records a single write removes beyond the retention. After the retention is lowered,
+/// the history shrinks to it over later writes rather than all in one transaction.
+const MAX_TRIM_PER_WRITE: u32 = 2;
+
+/// Bounds of the price history stored for an asset.
+///
+/// Each record lives in its own persistent entry under `DataKey::Record(asset, index)`, with
+/// indices assigned sequentially as records are written. Only the most recent `retention`
+/// records are kept; older entries are removed, a few at a time, as new ones are written.
+///
+/// Feeds deployed with the legacy layout keep each asset's history in a single
+/// `Map<timestamp, price>` under `DataKey::Prices(asset)`. Until `migrate` has moved them
+/// beneath the retained records, the records left in that map are read as the oldest part of
+/// the history.
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PriceHistory {
+    /// Index of the oldest retained record
+    pub first: u64,
+    /// Index the next record will be written to
+    pub next: u64,
+}
+
+impl PriceHistory {
+    /// Get the history bounds for an asset, if the asset is quoted by the feed
+    pub fn get(env: &Env, asset: &Asset) -> Option<PriceHistory> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::History(asset.clone()))
+    }
+
+    /// Get the history bounds for an asset, or empty bounds if none are stored. Empty bounds
+    /// leave room beneath them for the asset's legacy records, if any, to be migrated into.
+    pub fn load(env: &Env, asset: &Asset) -> PriceHistory {
+        let mut history = Self::get(env, asset).unwrap_or_default();
+        if history.is_empty()
+            && let Some(legacy) = Self::legacy(env, asset)
+        {
+            history.first = history.next + legacy.len() as u64;
+            history.next = history.first;
+        }
+        history
+    }
+
+    pub fn set(&self, env: &Env, asset: &Asset) {
+        let key = DataKey::History(asset.clone());
+        env.storage().persistent().set(&key, self);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+    }
+
+    /// Number of records currently retained
+    pub fn len(&self) -> u64 {
+        self.next - self.first
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn write_record(env: &Env, asset: &Asset, index: u64, record: &PriceData) {
+        let key = DataKey::Record(asset.clone(), index);
+        env.storage().persistent().set(&key, record);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+    }
+
+    /// Legacy records of an asset that are still to be migrated, all older than the retained ones
+    fn legacy(env: &Env, asset: &Asset) -> Option<Map<u64, i128>> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Prices(asset.clone()))
+    }
+
+    fn record(env: &Env, asset: &Asset, index: u64) -> PriceData {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Record(asset.clone(), index))
+            .unwrap()
+    }
+
+    /// The most recent record
+    pub fn last(&self, env: &Env, asset: &Asset) -> Option<PriceData> {
+        if self.is_empty() {
+            let legacy = Self::legacy(env, asset)?;
+            let timestamp = legacy.keys().last()?;
+            return Some(PriceData {
+                price: legacy.get_unchecked(timestamp),
+                timestamp,
+            });
+        }
+        Some(Self::record(env, asset, self.next - 1))
+    }
+
+    /// The most recent record at or before `timestamp`. Records are stored in ascending
+    /// timestamp order, so this is a binary search over the retained indices.
+    pub fn at_or_before(&self, env: &Env, asset: &Asset, timestamp: u64) -> Option<PriceData> {
+        let (mut low, mut high) = (self.first, self.next);
+        let mut found = None;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let record = Self::record(env, asset, mid);
+            if record.timestamp <= timestamp {
+                low = mid + 1;
+                found = Some(record);
+            } else {
+                high = mid;
+            }
+        }
+        found.or_else(|| {
+            let legacy = Self::legacy(env, asset)?;
+            let timestamps = legacy.keys();
+            let index = match timestamps.binary_search(timestamp) {
+                Ok(index) => index,
+                Err(0) => return None,
+                Err(index) => index - 1,
+            };
+            let timestamp = timestamps.get_unchecked(index);
+            Some(PriceData {
+                price: legacy.get_unchecked(timestamp),
+                timestamp,
+            })
+        })
+    }
+
+    /// Up to `records` of the most recent records, newest first
+    pub fn latest(&self, env: &Env, asset: &Asset, records: u32) -> Vec<PriceData> {
+        let mut prices = vec![env];
+        let mut index = self.next;
+        while index > self.first && prices.len() < records {
+            index -= 1;
+            prices.push_back(Self::record(env, asset, index));
+        }
+        if prices.len() < records
+            && let Some(legacy) = Self::legacy(env, asset)
+        {
+            let timestamps = legacy.keys();
+            let mut index = timestamps.len();
+            while index > 0 && prices.len() < records {
+                index -= 1;
+                let timestamp = timestamps.get_unchecked(index);
+                prices.push_back(PriceData {
+                    price: legacy.get_unchecked(timestamp),
+                    timestamp,
+                });
+            }
+        }
+        prices
+    }
+
+    /// Forget an asset's history by removing its bounds and any legacy records, a fixed number
+    /// of entries however long the history is. The records themselves are no longer read, and
+    /// are left to expire with their TTL or be overwritten if the asset is added again.
+    pub fn clear(env: &Env, asset: &Asset) {
+        env.storage()
+            .persistent()
+            .remove(&DataKey::History(asset.clone()));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Prices(asset.clone()));
+    }
+
+    /// Move up to `max_records` of the newest legacy records beneath the retained ones, which
+    /// must all be newer. Legacy records that would take the history beyond `retention` are
+    /// dropped. Returns the number of legacy records left to migrate.
+    pub fn migrate(&mut self, env: &Env, asset: &Asset, max_records: u32, retention: u64) -> u32 {
+        let key = DataKey::Prices(asset.clone());
+        let Some(mut legacy) = Self::legacy(env, asset) else {
+            return 0;
+        };
+        let timestamps = legacy.keys();
+        let mut index = timestamps.len();
+        while index > 0
+            && timestamps.len() - index < max_records
+            && self.first > 0
+            && self.len() < retention
+        {
+            index -= 1;
+            let timestamp = timestamps.get_unchecked(index);
+            let price = legacy.get_unchecked(timestamp);
+            self.first -= 1;
+            Self::write_record(env, asset, self.first, &PriceData { price, timestamp });
+            legacy.remove(timestamp);
+        }
+        if index < timestamps.len() {
+            self.set(env, asset);
+        }
+        if index == 0 || self.first == 0 || self.len() >= retention {
+            env.storage().persistent().remove(&key);
+            return 0;
+        }
+        env.storage().persistent().set(&key, &legacy);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+        index
+    }
+
+    /// Write a record, dropping up to `MAX_TRIM_PER_WRITE` of the oldest ones beyond
+    /// `retention`. A record for the same timestamp as the most recent one replaces it, leaving
+    /// the bounds as they are; older timestamps are rejected.
+    pub fn push(&mut self, env: &Env, asset: &Asset, record: &PriceData, retention: u64) {
+        match self.last(env, asset) {
+            Some(last) if last.timestamp == record.timestamp => {
+                Self::write_record(env, asset, self.next - 1, record);
+                return;
+            }
+            Some(last) if last.timestamp > record.timestamp => {
+                panic_with_error!(env, Error::TimestampOutOfOrder);
+            }
+            _ => {}
+        }
+        Self::write_record(env, asset, self.next, record);
+        self.next += 1;
+        let mut trimmed = 0;
+        while self.len() > retention && trimmed < MAX_TRIM_PER_WRITE {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Record(asset.clone(), self.first));
+            self.first += 1;
+            trimmed += 1;
+        }
+        self.set(env, asset);
+    }
+
+    /// Extend the TTL of the history bounds, of any legacy records still to be migrated, and of
+    /// up to `records` of the most recent records. Derived assets have no bounds stored, and
+    /// nothing to extend.
+    pub fn extend_ttl(&self, env: &Env, asset: &Asset, records: u32) {
+        let legacy_key = DataKey::Prices(asset.clone());
+        if env.storage().persistent().has(&legacy_key) {
+            env.storage().persistent().extend_ttl(
+                &legacy_key,
+                PERSISTENT_TTL_THRESHOLD,
+                PERSISTENT_TTL,
+            );
+        }
+        let key = DataKey::History(asset.clone());
+        if !env.storage().persistent().has(&key) {
+            return;
+        }
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+        let first = self.first.max(self.next.saturating_sub(records as u64));
+        for index in first..self.next {
+            env.storage().persistent().extend_ttl(
+                &DataKey::Record(asset.clone(), index),
+                PERSISTENT_TTL_THRESHOLD,
+                PERSISTENT_TTL,
+            );
+        }
+    }
+
+    /// Remove records timestamped before `timestamp`, always keeping the most recent one.
+    /// Returns the number of records removed.
+    pub fn prune(&mut self, env: &Env, asset: &Asset, timestamp: u64) -> u32 {
+        let mut removed = 0;
+        while self.len() > 1 && Self::record(env, asset, self.first).timestamp < timestamp {
+            env.storage()
+                .persistent()
+                .remove(&DataKey::Record(asset.clone(), self.first));
+            self.first += 1;
+            removed += 1;
+        }
+        if removed > 0 {
+            self.set(env, asset);
+        }
+        removed
+    }
+}
@@ -2,8 +2,18 @@
 
 use soroban_sdk::{self, Address, Symbol, contracttype};
 
+mod confidence;
 pub mod data_feed;
+mod delayed;
+mod derived;
+mod events;
+mod formula;
+mod guard;
+mod history;
+mod reporters;
 mod sep40;
+mod signed;
+mod staking;
 
 /// Quoted asset definition
 #[contracttype]
@@ -17,10 +27,31 @@ pub enum Asset {
 
 /// Price record definition
 #[contracttype]
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct PriceData {
     pub price: i128,    //asset price at given point in time
     pub timestamp: u64, //recording timestamp
 }
 
+/// Price record with a confidence interval
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PriceConfidence {
+    pub price: i128,      //asset price at given point in time
+    pub confidence: i128, //half-width of the interval around the price
+    pub timestamp: u64,   //recording timestamp
+}
+
+impl PriceConfidence {
+    /// Lowest price within the interval
+    pub fn lower(&self) -> i128 {
+        self.price.saturating_sub(self.confidence)
+    }
+
+    /// Highest price within the interval
+    pub fn upper(&self) -> i128 {
+        self.price.saturating_add(self.confidence)
+    }
+}
+
 mod test;
@@ -0,0 +1,122 @@
+use crate::Asset;
+use crate::staking::{Bond, StakingConfig};
+use soroban_sdk::{Address, Env, Vec};
+
+/// Interface for feeds whose official prices are aggregated from a whitelisted set of reporters.
+/// Each reporter submits a price for a given asset and timestamp (a "round"); once `quorum`
+/// reporters have submitted for the same round, the median of their submissions is recorded
+/// as the official price for that timestamp.
+pub trait IsMedianFeed {
+    /// Add reporters to the whitelist, allowing reporters removed earlier to bond again.
+    /// Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, or if a reporter is already whitelisted
+    fn add_reporters(env: &Env, reporters: Vec<Address>);
+
+    /// Remove reporters from the whitelist. They can't rejoin by bonding until the admin adds
+    /// them again. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, if a reporter is not whitelisted, or if
+    /// fewer reporters than the current quorum would remain
+    fn remove_reporters(env: &Env, reporters: Vec<Address>);
+
+    /// Set the number of submissions required to finalize a round. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, if the quorum is zero or larger than the
+    /// number of whitelisted reporters, or if it is below 2 while staking is enabled
+    fn set_quorum(env: &Env, quorum: u32);
+
+    /// Return the whitelisted reporters
+    fn reporters(env: &Env) -> Vec<Address>;
+
+    /// Return the number of submissions required to finalize a round
+    fn quorum(env: &Env) -> u32;
+
+    /// Submit a price for the round identified by `asset` and `timestamp`. Must be signed by `reporter`.
+    /// The submission that reaches quorum records the median of the round as the official price.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reporter` is not whitelisted, if it already submitted for this round, if
+    /// the round has already been finalized, or if `timestamp` could not be written by
+    /// `set_asset_price`
+    fn submit_price(env: &Env, reporter: Address, asset: Asset, price: i128, timestamp: u64);
+}
+
+/// Interface for reporters who join the whitelist by bonding tokens. When a round finalizes,
+/// bonded reporters whose submission is beyond the tolerance from the median lose part of
+/// their bond to the reward pool, and whitelisted ones within it earn a reward from the pool.
+/// Reporters whose bond falls below the minimum are removed from the whitelist; the admin
+/// should keep enough reporters bonded to meet quorum. Leaving takes two steps, `unbond` and
+/// then `withdraw_bond` once the unbonding period is over, so a reporter can't withdraw its
+/// bond ahead of a round that would slash it.
+pub trait IsStakedReporters {
+    /// Set the staking terms. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, if the minimum bond isn't positive, if the
+    /// reward is negative, if the slash exceeds 100%, if the unbonding period is shorter than
+    /// a round stays open, if the token differs from the one already configured, or if the
+    /// quorum is below 2
+    fn set_staking(env: &Env, config: StakingConfig);
+
+    /// Return the staking terms, if staking is enabled
+    fn staking(env: &Env) -> Option<StakingConfig>;
+
+    /// Bond `amount` tokens from `reporter`, joining the whitelist if it isn't bonded yet or
+    /// topping up its bond otherwise. Reporters whitelisted by the admin may post a bond too,
+    /// and an unbonding reporter bonding again stays in the whitelist. Must be signed by
+    /// `reporter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if staking is disabled, if `amount` isn't positive, if the admin removed
+    /// `reporter` from the whitelist, or if the resulting bond is below the minimum
+    fn bond(env: &Env, reporter: Address, amount: i128);
+
+    /// Leave the whitelist and start the unbonding period, after which `withdraw_bond` returns
+    /// the bond. Open rounds stop counting its submissions, but can still slash them. Must be
+    /// signed by `reporter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reporter` isn't bonded or is already unbonding, or if fewer reporters than
+    /// the quorum would remain
+    fn unbond(env: &Env, reporter: Address);
+
+    /// Return the bond and unclaimed rewards of a reporter whose unbonding period is over.
+    /// Must be signed by `reporter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reporter` isn't bonded, hasn't called `unbond`, or is still within the
+    /// unbonding period
+    fn withdraw_bond(env: &Env, reporter: Address);
+
+    /// Return a reporter's bond and unclaimed rewards
+    fn bond_of(env: &Env, reporter: Address) -> Option<Bond>;
+
+    /// Transfer rewards earned by `reporter` to it and return the amount. Must be signed by `reporter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if staking is disabled or `reporter` isn't bonded
+    fn claim_rewards(env: &Env, reporter: Address) -> i128;
+
+    /// Add `amount` tokens from `from` to the reward pool. Must be signed by `from`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if staking is disabled or `amount` isn't positive
+    fn fund_rewards(env: &Env, from: Address, amount: i128);
+
+    /// Return the tokens available to pay rewards
+    fn reward_pool(env: &Env) -> i128;
+}
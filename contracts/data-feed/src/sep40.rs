@@ -15,16 +15,50 @@ pub trait IsSep40 {
     /// Get the most recent price for an asset
     fn lastprice(env: &Env, asset: Asset) -> Option<PriceData>;
 
-    /// Get price in base asset at specific timestamp
+    /// Get price in base asset at specific timestamp: the most recent record at or before the
+    /// start of the tick containing `timestamp`
     fn price(env: &Env, asset: Asset, timestamp: u64) -> Option<PriceData>;
 
     /// Get last N price records
     fn prices(env: &Env, asset: Asset, records: u32) -> Option<Vec<PriceData>>;
 
-    /// Return default tick period timeframe (in milliseconds)
+    /// Return default tick period timeframe (in seconds)
     fn resolution(env: &Env) -> u32;
 }
 
+/// Cross-price extensions to the consumer interface, as offered by Reflector oracles. Each
+/// function quotes `base_asset` in terms of `quote_asset`, using the feed's `decimals`.
+pub trait IsSep40Cross {
+    /// Get the most recent cross price; its timestamp is the older of the two underlying records
+    fn x_last_price(env: &Env, base_asset: Asset, quote_asset: Asset) -> Option<PriceData>;
+
+    /// Get the cross price at specific timestamp, resolved as in `price`
+    fn x_price(
+        env: &Env,
+        base_asset: Asset,
+        quote_asset: Asset,
+        timestamp: u64,
+    ) -> Option<PriceData>;
+
+    /// Get cross prices for the last N records of `base_asset`, newest first
+    fn x_prices(
+        env: &Env,
+        base_asset: Asset,
+        quote_asset: Asset,
+        records: u32,
+    ) -> Option<Vec<PriceData>>;
+}
+
+/// Time-weighted average price views. Each record is weighted by how long it remained the
+/// latest price: until the next record, or one resolution tick for the most recent record.
+pub trait IsSep40Twap {
+    /// Get the time-weighted average price over the last N records
+    fn twap(env: &Env, asset: Asset, records: u32) -> Option<i128>;
+
+    /// Get the time-weighted average cross price over the last N records of `base_asset`
+    fn x_twap(env: &Env, base_asset: Asset, quote_asset: Asset, records: u32) -> Option<i128>;
+}
+
 /// While not part of the official consumer-facing spec, every SEP40 contract will need
 /// to provide a way for Oracles to update the contract with new prices. This is an interface for
 /// that, and also for other administrative functions, like initializing the contract.
@@ -33,13 +67,85 @@ pub trait IsSep40Admin {
     ///
     /// # Panics
     ///
-    /// Panics if the caller doesn't match admin, or if the assets are already added
+    /// Panics if the caller doesn't match admin, or if the assets are already added. Deprecated
+    /// assets may be added again.
     fn add_assets(env: &Env, assets: Vec<Asset>);
 
+    /// Stop quoting an asset: its price can no longer be updated and `lastprice` fails with
+    /// `AssetDeprecated`, while its history stays readable through `price` and `prices`.
+    /// Adding the asset again resumes updates. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, if the asset is not quoted by the contract, or
+    /// if a derived asset's formula reads it
+    fn deprecate_asset(env: &Env, asset: Asset);
+
+    /// Remove a quoted or deprecated asset from the contract. Its history is discarded if
+    /// `clear_history` is set, leaving the records to expire, and otherwise kept to be resumed
+    /// if the asset is added again.
+    /// Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, if the asset is neither quoted nor deprecated,
+    /// or if a derived asset's formula reads it
+    fn remove_asset(env: &Env, asset: Asset, clear_history: bool);
+
     /// Record new price feed history snapshot. Can be invoked only by the admin account.
     ///
     /// # Panics
     ///
-    /// Panics if the caller doesn't match admin address
+    /// Panics if the caller doesn't match admin address, if `timestamp` is not a multiple of
+    /// the resolution, is older than the most recent record, or is more than one tick ahead
+    /// of the ledger time, or if the price is outside the asset's circuit breaker limits
     fn set_asset_price(env: &Env, asset: Asset, price: i128, timestamp: u64);
+
+    /// Record prices for several assets under a single timestamp. Either every price is written
+    /// or none is. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin address, if any asset is not quoted by the
+    /// contract, or if `timestamp` could not be written by `set_asset_price`
+    fn set_asset_prices(env: &Env, prices: Vec<(Asset, i128)>, timestamp: u64);
+
+    /// Set the number of records kept per asset; older records are dropped as new ones are
+    /// written. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin address, or if `retention` is zero
+    fn set_retention(env: &Env, retention: u32);
+
+    /// Remove an asset's price records timestamped before `before_timestamp`, e.g. to free
+    /// storage ahead of retention trimming. Only records older than the retention window,
+    /// `retention` ticks back from the most recent record, may be pruned, and the most recent
+    /// record is always kept. Returns the number of records removed. Can be invoked only by
+    /// the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin address, if the asset is not found, or if
+    /// `before_timestamp` is within the retention window
+    fn prune(env: &Env, asset: Asset, before_timestamp: u64) -> u32;
+
+    /// Set the circuit breaker limits for an asset: the maximum change from the previous record
+    /// in basis points, and the maximum time since the previous record in seconds for it to be
+    /// checked against. After a longer gap, e.g. an outage, the next update is accepted without
+    /// the deviation check and a `price_gap_detected` event is emitted, so no admin action is
+    /// needed to resume the feed. Zero disables a limit. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin address, or if the asset is not quoted by the contract
+    fn set_price_guard(env: &Env, asset: Asset, max_deviation: u32, max_gap: u64);
+
+    /// Record a price without applying the asset's circuit breaker limits. Can be invoked only
+    /// by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the same conditions as `set_asset_price`, other than the circuit breaker
+    fn force_set_asset_price(env: &Env, asset: Asset, price: i128, timestamp: u64);
 }
@@ -0,0 +1,45 @@
+use crate::Asset;
+use soroban_sdk::{BytesN, Env, Vec};
+
+/// Interface for prices signed off-chain by registered ed25519 oracle keys. A signed price can
+/// be relayed by any account, so the signing key never needs to hold XLM or sign Stellar
+/// transactions.
+///
+/// The signed message is the XDR encoding of the tuple
+/// `(feed_contract_address, asset, price, timestamp)`, so a payload signed for one feed can't
+/// be replayed against another.
+pub trait IsSignedFeed {
+    /// Register ed25519 public keys allowed to sign prices. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, or if a key is already registered
+    fn add_signers(env: &Env, signers: Vec<BytesN<32>>);
+
+    /// Unregister signing keys. Can be invoked only by the admin account.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the caller doesn't match admin, or if a key is not registered
+    fn remove_signers(env: &Env, signers: Vec<BytesN<32>>);
+
+    /// Return the registered signing keys
+    fn signers(env: &Env) -> Vec<BytesN<32>>;
+
+    /// Record a price signed by `signer`. Can be invoked by any account. The first valid
+    /// payload for a timestamp is final, so later payloads for the same timestamp are rejected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `signer` is not registered, if `signature` doesn't verify, if the asset
+    /// already has a price for `timestamp`, or if `timestamp` could not be written by
+    /// `set_asset_price`
+    fn submit_signed_price(
+        env: &Env,
+        signer: BytesN<32>,
+        asset: Asset,
+        price: i128,
+        timestamp: u64,
+        signature: BytesN<64>,
+    );
+}
@@ -0,0 +1,92 @@
+use soroban_sdk::{Address, Env, Symbol, contracttype, symbol_short};
+
+use crate::data_feed::{DataKey, PERSISTENT_TTL, PERSISTENT_TTL_THRESHOLD};
+
+const STAKING: Symbol = symbol_short!("STAKING");
+const POOL: Symbol = symbol_short!("POOL");
+const BASIS_POINTS: i128 = 10_000;
+
+/// Terms for reporters who bond tokens to join the reporter set
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StakingConfig {
+    /// Token bonds and rewards are paid in, e.g. the XLM Stellar Asset Contract
+    pub token: Address,
+    /// Smallest bond a reporter may hold
+    pub min_bond: i128,
+    /// Largest accepted distance of a submission from the finalized median, in basis points
+    pub tolerance: u32,
+    /// Share of the bond taken for a submission beyond the tolerance, in basis points
+    pub slash: u32,
+    /// Reward for each submission within the tolerance, paid from the reward pool
+    pub reward: i128,
+    /// Ledgers an unbonding reporter waits before withdrawing, during which submissions it
+    /// made to open rounds can still be slashed. At least as long as a round stays open.
+    pub unbonding_period: u32,
+}
+
+impl StakingConfig {
+    pub fn get(env: &Env) -> Option<StakingConfig> {
+        env.storage().instance().get(&STAKING)
+    }
+
+    pub fn set(&self, env: &Env) {
+        env.storage().instance().set(&STAKING, self);
+    }
+
+    /// Whether `price` is within the tolerance of `median`
+    pub fn accepts(&self, price: i128, median: i128) -> bool {
+        let difference = price.abs_diff(median);
+        let Some(scaled) = difference.checked_mul(BASIS_POINTS as u128) else {
+            return false;
+        };
+        scaled <= median.unsigned_abs().saturating_mul(self.tolerance as u128)
+    }
+
+    /// Amount taken from `bond` for an inaccurate submission
+    pub fn slash_amount(&self, bond: i128) -> i128 {
+        bond / BASIS_POINTS * self.slash as i128
+            + bond % BASIS_POINTS * self.slash as i128 / BASIS_POINTS
+    }
+}
+
+/// Tokens a reporter has bonded, and rewards earned but not yet claimed
+#[contracttype]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bond {
+    pub amount: i128,
+    pub rewards: i128,
+    /// Ledger from which an unbonding reporter may withdraw; 0 while bonded
+    pub unlocks_at: u32,
+}
+
+impl Bond {
+    pub fn get(env: &Env, reporter: &Address) -> Option<Bond> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Bond(reporter.clone()))
+    }
+
+    pub fn set(&self, env: &Env, reporter: &Address) {
+        let key = DataKey::Bond(reporter.clone());
+        env.storage().persistent().set(&key, self);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, PERSISTENT_TTL_THRESHOLD, PERSISTENT_TTL);
+    }
+
+    pub fn remove(env: &Env, reporter: &Address) {
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Bond(reporter.clone()));
+    }
+}
+
+/// Tokens available to pay rewards, funded by deposits and slashed bonds
+pub fn reward_pool(env: &Env) -> i128 {
+    env.storage().instance().get(&POOL).unwrap_or(0)
+}
+
+pub fn set_reward_pool(env: &Env, amount: i128) {
+    env.storage().instance().set(&POOL, &amount);
+}
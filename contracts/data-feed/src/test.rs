@@ -1,10 +1,18 @@
 #![cfg(test)]
 extern crate std;
 use crate::Asset;
-use crate::data_feed::{DataFeed, DataFeedClient, Error};
+use crate::data_feed::{DataFeed, DataFeedClient, DataKey, Error};
+use crate::formula::Formula;
+use crate::staking::{Bond, StakingConfig};
 
-use soroban_sdk::{Address, Env, testutils::Address as _};
-use soroban_sdk::{Symbol, Vec};
+use ed25519_dalek::{Signer, SigningKey};
+use soroban_sdk::{
+    Address, BytesN, Env, IntoVal,
+    testutils::{Address as _, Events, Ledger, storage::Persistent as _},
+    token,
+    xdr::ToXdr,
+};
+use soroban_sdk::{Map, Symbol, Val, Vec, map, vec};
 
 fn create_datafeed_contract<'a>(e: &Env) -> DataFeedClient<'a> {
     let asset_xlm: Asset = Asset::Other(Symbol::new(e, "XLM"));
@@ -27,6 +35,7 @@ fn test_data_feed() {
     // Not in initial assets
     let asset_xeur: Asset = Asset::Other(Symbol::new(&e, "XEUR"));
     let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(1_000_001_100);
 
     // Test add_assets
     datafeed.add_assets(&Vec::from_array(&e, [asset_xeur.clone()]));
@@ -56,7 +65,7 @@ fn test_data_feed() {
     assert_eq!(datafeed.resolution(), 300);
 
     // Test set_asset_price and price
-    let timestamp1: u64 = 1_000_000_000;
+    let timestamp1: u64 = 1_000_000_200;
     let price1 = 10_000_000;
     datafeed.set_asset_price(&asset_xlm, &price1, &timestamp1);
     assert_eq!(
@@ -70,7 +79,7 @@ fn test_data_feed() {
     assert_eq!(last_price.timestamp, timestamp1);
 
     // Test prices (multiple records)
-    let timestamp2: u64 = 1_000_001_000;
+    let timestamp2: u64 = 1_000_001_100;
     let price2 = 10_500_000;
     datafeed.set_asset_price(&asset_xlm, &price2, &timestamp2);
 
@@ -98,11 +107,1061 @@ fn test_data_feed() {
     let result = datafeed.try_prices(&non_existent_asset, &1);
     assert!(result.is_err());
 
-    // Test price at non-existent timestamp
-    let non_existent_timestamp: u64 = 2_000_000_000;
+    // Test price between records returns the most recent record at or before it
+    let between = datafeed.price(&asset_xlm, &(timestamp1 + 450)).unwrap();
+    assert_eq!(between.price, price1);
+    assert_eq!(between.timestamp, timestamp1);
+
+    // Test price before the first record
+    let non_existent_timestamp: u64 = 1_000_000_000;
     assert!(
         datafeed
             .price(&asset_xlm, &non_existent_timestamp)
             .is_none()
     );
 }
+
+#[test]
+fn test_timestamp_validation() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xusd: Asset = Asset::Other(Symbol::new(&e, "XUSD"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(10_050);
+
+    // Timestamps must be multiples of the resolution
+    let result = datafeed.try_set_asset_price(&asset_xlm, &1, &9_950);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::TimestampNotAligned.into()
+    );
+
+    // At most one tick ahead of the ledger time
+    let result = datafeed.try_set_asset_price(&asset_xlm, &1, &10_500);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::TimestampInFuture.into()
+    );
+    datafeed.set_asset_price(&asset_xlm, &1, &10_200);
+
+    // Writes older than the asset's last record are rejected, while an asset that lags
+    // behind others can still catch up
+    let result = datafeed.try_set_asset_price(&asset_xlm, &1, &9_900);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::TimestampOutOfOrder.into()
+    );
+    datafeed.set_asset_price(&asset_xusd, &1, &9_900);
+    datafeed.set_asset_price(&asset_xusd, &1, &10_200);
+    assert_eq!(
+        datafeed.price(&asset_xusd, &9_900).unwrap().timestamp,
+        9_900
+    );
+}
+
+#[test]
+fn test_median_reporting() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    let reporters: std::vec::Vec<Address> = (0..4).map(|_| Address::generate(&e)).collect();
+    e.ledger().set_timestamp(1_000_000_500);
+    let outsider = Address::generate(&e);
+
+    // Quorum can't exceed the number of reporters
+    let result = datafeed.try_set_quorum(&1);
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidQuorum.into());
+
+    datafeed.add_reporters(&Vec::from_slice(&e, &reporters[..3]));
+    let result = datafeed.try_add_reporters(&Vec::from_array(&e, [reporters[0].clone()]));
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::ReporterAlreadyExists.into()
+    );
+    datafeed.add_reporters(&Vec::from_array(&e, [reporters[3].clone()]));
+    assert_eq!(datafeed.reporters().len(), 4);
+    datafeed.set_quorum(&3);
+    assert_eq!(datafeed.quorum(), 3);
+
+    // Only whitelisted reporters may submit
+    let timestamp: u64 = 1_000_000_200;
+    let result = datafeed.try_submit_price(&outsider, &asset_xlm, &1, &timestamp);
+    assert_eq!(result.unwrap_err().unwrap(), Error::NotReporter.into());
+
+    // Nothing is recorded until quorum is reached
+    datafeed.submit_price(&reporters[0], &asset_xlm, &10_000_000, &timestamp);
+    let result = datafeed.try_submit_price(&reporters[0], &asset_xlm, &10_000_000, &timestamp);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AlreadySubmitted.into());
+    datafeed.submit_price(&reporters[1], &asset_xlm, &99_000_000, &timestamp);
+    assert!(datafeed.lastprice(&asset_xlm).is_none());
+
+    // The third submission finalizes the round with the median
+    datafeed.submit_price(&reporters[2], &asset_xlm, &10_200_000, &timestamp);
+    // The last event records the aggregated price and the reporters who contributed to it
+    let events = e.events().all();
+    // Submissions are keyed by reporter, so they are listed in address order
+    let contributors = Map::from_array(
+        &e,
+        [
+            (reporters[0].clone(), true),
+            (reporters[1].clone(), true),
+            (reporters[2].clone(), true),
+        ],
+    )
+    .keys();
+    assert_eq!(
+        vec![&e, events.last().unwrap()],
+        vec![
+            &e,
+            (
+                datafeed.address.clone(),
+                (Symbol::new(&e, "price_aggregated"), asset_xlm.clone()).into_val(&e),
+                map![
+                    &e,
+                    (
+                        Symbol::new(&e, "price"),
+                        IntoVal::<Env, Val>::into_val(&10_200_000i128, &e)
+                    ),
+                    (
+                        Symbol::new(&e, "timestamp"),
+                        IntoVal::<Env, Val>::into_val(&timestamp, &e)
+                    ),
+                    (Symbol::new(&e, "reporters"), contributors.to_val()),
+                ]
+                .into_val(&e),
+            )
+        ]
+    );
+    let last_price = datafeed.lastprice(&asset_xlm).unwrap();
+    assert_eq!(last_price.price, 10_200_000);
+    assert_eq!(last_price.timestamp, timestamp);
+
+    // Late submissions are rejected once the round is finalized
+    let result = datafeed.try_submit_price(&reporters[3], &asset_xlm, &10_000_000, &timestamp);
+    assert_eq!(result.unwrap_err().unwrap(), Error::RoundFinalized.into());
+
+    // An even number of submissions averages the two middle values
+    datafeed.set_quorum(&4);
+    let timestamp2 = timestamp + 300;
+    for (reporter, price) in reporters.iter().zip([10, 20, 31, 1_000]) {
+        datafeed.submit_price(reporter, &asset_xlm, &price, &timestamp2);
+    }
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 25);
+
+    // Rounds are ordered per asset, so one for an asset that lags behind still finalizes
+    let asset_xusd: Asset = Asset::Other(Symbol::new(&e, "XUSD"));
+    for reporter in reporters.iter() {
+        datafeed.submit_price(reporter, &asset_xusd, &100, &timestamp);
+    }
+    let last_price = datafeed.lastprice(&asset_xusd).unwrap();
+    assert_eq!((last_price.price, last_price.timestamp), (100, timestamp));
+
+    // Reporters can't be removed below quorum
+    let result = datafeed.try_remove_reporters(&Vec::from_array(&e, [reporters[3].clone()]));
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidQuorum.into());
+    datafeed.set_quorum(&3);
+    datafeed.remove_reporters(&Vec::from_array(&e, [reporters[3].clone()]));
+    let result = datafeed.try_remove_reporters(&Vec::from_array(&e, [reporters[3].clone()]));
+    assert_eq!(result.unwrap_err().unwrap(), Error::NotReporter.into());
+    assert_eq!(datafeed.reporters().len(), 3);
+}
+
+#[test]
+fn test_retention() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(3_000);
+    assert_eq!(datafeed.retention(), 1_000);
+
+    let result = datafeed.try_set_retention(&0);
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidRetention.into());
+    datafeed.set_retention(&3);
+
+    for i in 0..5u64 {
+        datafeed.set_asset_price(&asset_xlm, &(100 + i as i128), &(1_200 + i * 300));
+    }
+    // Only the three most recent records are kept
+    let prices = datafeed.prices(&asset_xlm, &10).unwrap();
+    assert_eq!(prices.len(), 3);
+    assert_eq!(prices.get(0).unwrap().price, 104);
+    assert_eq!(prices.get(2).unwrap().price, 102);
+    assert!(datafeed.price(&asset_xlm, &1_500).is_none());
+    assert_eq!(datafeed.price(&asset_xlm, &1_800).unwrap().price, 102);
+
+    // Writing the latest timestamp again replaces the record
+    datafeed.set_asset_price(&asset_xlm, &200, &2_400);
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 200);
+    assert_eq!(datafeed.prices(&asset_xlm, &10).unwrap().len(), 3);
+
+    // Older timestamps are rejected
+    let result = datafeed.try_set_asset_price(&asset_xlm, &1, &2_100);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::TimestampOutOfOrder.into()
+    );
+
+    // Shrinking retention trims history a couple of records per write
+    datafeed.set_retention(&1);
+    datafeed.set_asset_price(&asset_xlm, &300, &2_700);
+    let prices = datafeed.prices(&asset_xlm, &10).unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices.get(0).unwrap().price, 300);
+    datafeed.set_asset_price(&asset_xlm, &301, &3_000);
+    let prices = datafeed.prices(&asset_xlm, &10).unwrap();
+    assert_eq!(prices.len(), 1);
+    assert_eq!(prices.get(0).unwrap().price, 301);
+}
+
+/// Store an asset's history the way feeds deployed with the single-Map layout did
+fn set_legacy_prices(e: &Env, datafeed: &DataFeedClient, asset: &Asset, legacy: Map<u64, i128>) {
+    e.as_contract(&datafeed.address, || {
+        e.storage()
+            .persistent()
+            .remove(&DataKey::History(asset.clone()));
+        e.storage()
+            .persistent()
+            .set(&DataKey::Prices(asset.clone()), &legacy);
+    });
+}
+
+#[test]
+fn test_migrate_legacy_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+    e.ledger().set_timestamp(1_800);
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    datafeed.set_retention(&3);
+    set_legacy_prices(
+        &e,
+        &datafeed,
+        &asset_xlm,
+        Map::from_array(&e, [(600u64, 9i128), (900, 10), (1_200, 11), (1_500, 12)]),
+    );
+
+    // Reads are the same before, during and after the migration
+    let check_reads = || {
+        let last_price = datafeed.lastprice(&asset_xlm).unwrap();
+        assert_eq!((last_price.price, last_price.timestamp), (12, 1_500));
+        assert_eq!(datafeed.price(&asset_xlm, &1_400).unwrap().price, 11);
+        assert_eq!(datafeed.price(&asset_xlm, &900).unwrap().price, 10);
+        let prices = datafeed.prices(&asset_xlm, &3).unwrap();
+        assert_eq!(prices.len(), 3);
+        assert_eq!(prices.get(0).unwrap().price, 12);
+        assert_eq!(prices.get(2).unwrap().price, 10);
+    };
+    check_reads();
+    assert_eq!(datafeed.prices(&asset_xlm, &10).unwrap().len(), 4);
+
+    // Each call migrates a bounded chunk, newest first, and resumes where the last left off
+    assert_eq!(datafeed.migrate_prices(&asset_xlm, &2), 2);
+    check_reads();
+    assert_eq!(datafeed.prices(&asset_xlm, &10).unwrap().len(), 4);
+
+    // Records beyond the retention are dropped
+    assert_eq!(datafeed.migrate_prices(&asset_xlm, &2), 0);
+    check_reads();
+    assert_eq!(datafeed.prices(&asset_xlm, &10).unwrap().len(), 3);
+    assert!(datafeed.price(&asset_xlm, &600).is_none());
+    e.as_contract(&datafeed.address, || {
+        assert!(
+            !e.storage()
+                .persistent()
+                .has(&DataKey::Prices(asset_xlm.clone()))
+        );
+    });
+    assert_eq!(datafeed.migrate_prices(&asset_xlm, &2), 0);
+}
+
+#[test]
+fn test_write_before_migrating_legacy_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+    e.ledger().set_timestamp(1_800);
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    set_legacy_prices(
+        &e,
+        &datafeed,
+        &asset_xlm,
+        Map::from_array(&e, [(900u64, 10i128), (1_200, 11)]),
+    );
+
+    // Writing doesn't migrate, and the legacy records are read beneath the new one
+    let result = datafeed.try_set_asset_price(&asset_xlm, &12, &900);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::TimestampOutOfOrder.into()
+    );
+    datafeed.set_asset_price(&asset_xlm, &12, &1_800);
+    let prices = datafeed.prices(&asset_xlm, &3).unwrap();
+    assert_eq!(prices.len(), 3);
+    assert_eq!(prices.get(0).unwrap().price, 12);
+    assert_eq!(prices.get(2).unwrap().price, 10);
+    assert_eq!(datafeed.price(&asset_xlm, &1_500).unwrap().price, 11);
+
+    assert_eq!(datafeed.migrate_prices(&asset_xlm, &10), 0);
+    assert_eq!(datafeed.prices(&asset_xlm, &3).unwrap(), prices);
+}
+
+#[test]
+fn test_set_asset_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xusd: Asset = Asset::Other(Symbol::new(&e, "XUSD"));
+    let asset_xeur: Asset = Asset::Other(Symbol::new(&e, "XEUR"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(1_200);
+
+    let prices = Vec::from_array(
+        &e,
+        [
+            (asset_xlm.clone(), 10_000_000),
+            (asset_xusd.clone(), 100_000_000),
+        ],
+    );
+    datafeed.set_asset_prices(&prices, &1_200);
+    assert_eq!(
+        e.events().all(),
+        vec![
+            &e,
+            (
+                datafeed.address.clone(),
+                (Symbol::new(&e, "prices_updated"), 1_200u64).into_val(&e),
+                map![
+                    &e,
+                    (Symbol::new(&e, "prices"), prices.to_val()),
+                    (Symbol::new(&e, "reporter"), datafeed.admin().to_val()),
+                ]
+                .into_val(&e),
+            ),
+        ]
+    );
+    let xlm = datafeed.lastprice(&asset_xlm).unwrap();
+    let xusd = datafeed.lastprice(&asset_xusd).unwrap();
+    assert_eq!((xlm.price, xlm.timestamp), (10_000_000, 1_200));
+    assert_eq!((xusd.price, xusd.timestamp), (100_000_000, 1_200));
+
+    // An unknown asset rejects the whole batch
+    e.ledger().set_timestamp(1_500);
+    let result = datafeed.try_set_asset_prices(
+        &Vec::from_array(&e, [(asset_xlm.clone(), 1), (asset_xeur.clone(), 1)]),
+        &1_500,
+    );
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetNotFound.into());
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().timestamp, 1_200);
+}
+
+#[test]
+fn test_cross_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xusd: Asset = Asset::Other(Symbol::new(&e, "XUSD"));
+    let asset_xeur: Asset = Asset::Other(Symbol::new(&e, "XEUR"));
+    let datafeed = create_datafeed_contract(&e);
+    datafeed.add_assets(&Vec::from_array(&e, [asset_xeur.clone()]));
+    e.ledger().set_timestamp(1_800);
+
+    // No cross price until both assets have records
+    assert!(datafeed.x_last_price(&asset_xlm, &asset_xusd).is_none());
+
+    // XLM = 0.1 USD, then 0.12 USD; USD = 1 USD. 14 decimals.
+    datafeed.set_asset_price(&asset_xlm, &10_000_000_000_000, &1_200);
+    datafeed.set_asset_price(&asset_xusd, &100_000_000_000_000, &1_200);
+    datafeed.set_asset_price(&asset_xlm, &12_000_000_000_000, &1_500);
+
+    // USD per XLM, timestamped with the older record
+    let cross = datafeed.x_last_price(&asset_xlm, &asset_xusd).unwrap();
+    assert_eq!(cross.price, 12_000_000_000_000);
+    assert_eq!(cross.timestamp, 1_200);
+
+    // XLM per USD
+    let cross = datafeed.x_last_price(&asset_xusd, &asset_xlm).unwrap();
+    assert_eq!(cross.price, 833_333_333_333_333);
+
+    // Historical cross prices resolve each side at or before the timestamp
+    let cross = datafeed.x_price(&asset_xlm, &asset_xusd, &1_499).unwrap();
+    assert_eq!(cross.price, 10_000_000_000_000);
+    assert!(datafeed.x_price(&asset_xlm, &asset_xusd, &900).is_none());
+
+    let crosses = datafeed.x_prices(&asset_xlm, &asset_xusd, &5).unwrap();
+    assert_eq!(crosses.len(), 2);
+    assert_eq!(crosses.get(0).unwrap().price, 12_000_000_000_000);
+    assert_eq!(crosses.get(1).unwrap().price, 10_000_000_000_000);
+
+    // A zero quote price has no cross price
+    datafeed.set_asset_price(&asset_xeur, &0, &1_500);
+    assert!(datafeed.x_last_price(&asset_xlm, &asset_xeur).is_none());
+}
+
+#[test]
+fn test_twap() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xusd: Asset = Asset::Other(Symbol::new(&e, "XUSD"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(3_000);
+
+    assert!(datafeed.twap(&asset_xlm, &3).is_none());
+
+    // 100 for two ticks, then 160 for one tick (missed tick at 1_500), then 400
+    datafeed.set_asset_price(&asset_xusd, &1_000, &1_200);
+    datafeed.set_asset_price(&asset_xlm, &100, &1_200);
+    datafeed.set_asset_price(&asset_xlm, &160, &1_800);
+    datafeed.set_asset_price(&asset_xlm, &400, &2_100);
+
+    // The newest record counts for a single tick
+    assert_eq!(datafeed.twap(&asset_xlm, &1), Some(400));
+    // (160 * 300 + 400 * 300) / 600
+    assert_eq!(datafeed.twap(&asset_xlm, &2), Some(280));
+    // (100 * 600 + 160 * 300 + 400 * 300) / 1_200
+    assert_eq!(datafeed.twap(&asset_xlm, &3), Some(190));
+    assert_eq!(datafeed.twap(&asset_xlm, &10), Some(190));
+
+    // Quoted in XUSD at 1_000 (with 14 decimals), the cross TWAP scales accordingly
+    assert_eq!(
+        datafeed.x_twap(&asset_xlm, &asset_xusd, &3),
+        Some(19_000_000_000_000)
+    );
+}
+
+#[test]
+fn test_price_guard() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xeur: Asset = Asset::Other(Symbol::new(&e, "XEUR"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(6_000);
+
+    let result = datafeed.try_set_price_guard(&asset_xeur, &1_000, &600);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetNotFound.into());
+
+    // 10% per update, at most two ticks apart
+    datafeed.set_price_guard(&asset_xlm, &1_000, &600);
+    assert_eq!(datafeed.price_guard(&asset_xlm).max_deviation, 1_000);
+
+    // The first record has nothing to be checked against
+    datafeed.set_asset_price(&asset_xlm, &1_000, &1_200);
+    datafeed.set_asset_price(&asset_xlm, &1_100, &1_500);
+    datafeed.set_asset_price(&asset_xlm, &990, &1_800);
+
+    let result = datafeed.try_set_asset_price(&asset_xlm, &100, &2_100);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::PriceDeviationExceeded.into()
+    );
+    // The admin can force an update through, which is logged
+    datafeed.force_set_asset_price(&asset_xlm, &100, &2_100);
+    assert_eq!(
+        e.events().all(),
+        vec![
+            &e,
+            (
+                datafeed.address.clone(),
+                (Symbol::new(&e, "price_forced"), asset_xlm.clone()).into_val(&e),
+                map![
+                    &e,
+                    (
+                        Symbol::new(&e, "price"),
+                        IntoVal::<Env, Val>::into_val(&100i128, &e)
+                    ),
+                    (
+                        Symbol::new(&e, "timestamp"),
+                        IntoVal::<Env, Val>::into_val(&2_100u64, &e)
+                    ),
+                    (Symbol::new(&e, "reporter"), datafeed.admin().to_val()),
+                ]
+                .into_val(&e),
+            ),
+        ]
+    );
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 100);
+
+    // After an outage longer than the maximum gap the feed resumes without the deviation check
+    datafeed.set_asset_price(&asset_xlm, &1_000, &3_000);
+    assert_eq!(
+        e.events().all(),
+        vec![
+            &e,
+            (
+                datafeed.address.clone(),
+                (Symbol::new(&e, "price_gap_detected"), asset_xlm.clone()).into_val(&e),
+                map![
+                    &e,
+                    (
+                        Symbol::new(&e, "previous_timestamp"),
+                        IntoVal::<Env, Val>::into_val(&2_100u64, &e)
+                    ),
+                    (
+                        Symbol::new(&e, "timestamp"),
+                        IntoVal::<Env, Val>::into_val(&3_000u64, &e)
+                    ),
+                ]
+                .into_val(&e),
+            ),
+            (
+                datafeed.address.clone(),
+                (Symbol::new(&e, "price_updated"), asset_xlm.clone()).into_val(&e),
+                map![
+                    &e,
+                    (
+                        Symbol::new(&e, "price"),
+                        IntoVal::<Env, Val>::into_val(&1_000i128, &e)
+                    ),
+                    (
+                        Symbol::new(&e, "timestamp"),
+                        IntoVal::<Env, Val>::into_val(&3_000u64, &e)
+                    ),
+                    (Symbol::new(&e, "reporter"), datafeed.admin().to_val()),
+                ]
+                .into_val(&e),
+            ),
+        ]
+    );
+    let result = datafeed.try_set_asset_price(&asset_xlm, &100, &3_300);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::PriceDeviationExceeded.into()
+    );
+
+    // Limits of zero disable the checks
+    datafeed.set_price_guard(&asset_xlm, &0, &0);
+    datafeed.set_asset_price(&asset_xlm, &1_000_000, &3_300);
+}
+
+#[test]
+fn test_deprecate_and_remove_assets() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xusd: Asset = Asset::Other(Symbol::new(&e, "XUSD"));
+    let asset_xeur: Asset = Asset::Other(Symbol::new(&e, "XEUR"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(1_500);
+    datafeed.set_asset_price(&asset_xlm, &10, &1_200);
+    datafeed.set_asset_price(&asset_xusd, &20, &1_200);
+
+    let result = datafeed.try_deprecate_asset(&asset_xeur);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetNotFound.into());
+
+    // Deprecated assets can't be updated or quoted, but their history stays readable
+    datafeed.deprecate_asset(&asset_xlm);
+    assert_eq!(datafeed.assets(), Vec::from_array(&e, [asset_xusd.clone()]));
+    assert_eq!(
+        datafeed.deprecated_assets(),
+        Vec::from_array(&e, [asset_xlm.clone()])
+    );
+    let result = datafeed.try_lastprice(&asset_xlm);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetDeprecated.into());
+    let result = datafeed.try_set_asset_price(&asset_xlm, &11, &1_500);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetDeprecated.into());
+    assert_eq!(datafeed.price(&asset_xlm, &1_200).unwrap().price, 10);
+    assert_eq!(datafeed.prices(&asset_xlm, &5).unwrap().len(), 1);
+
+    // Adding it again resumes updates on top of the existing history
+    datafeed.add_assets(&Vec::from_array(&e, [asset_xlm.clone()]));
+    assert!(datafeed.deprecated_assets().is_empty());
+    datafeed.set_asset_price(&asset_xlm, &11, &1_500);
+    assert_eq!(datafeed.prices(&asset_xlm, &5).unwrap().len(), 2);
+
+    // Removing without clearing keeps the history for a later re-add
+    datafeed.remove_asset(&asset_xlm, &false);
+    let result = datafeed.try_lastprice(&asset_xlm);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetNotFound.into());
+    datafeed.add_assets(&Vec::from_array(&e, [asset_xlm.clone()]));
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 11);
+
+    // Removing with clearing discards it, in a bounded number of writes however long it is
+    for i in 0..20u64 {
+        e.ledger().set_timestamp(1_500 + i * 300);
+        datafeed.set_asset_price(&asset_xusd, &20, &(1_500 + i * 300));
+    }
+    datafeed.deprecate_asset(&asset_xusd);
+    datafeed.remove_asset(&asset_xusd, &true);
+    assert!(e.cost_estimate().resources().write_entries < 10);
+    assert!(datafeed.deprecated_assets().is_empty());
+    let result = datafeed.try_remove_asset(&asset_xusd, &true);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetNotFound.into());
+    datafeed.add_assets(&Vec::from_array(&e, [asset_xusd.clone()]));
+    assert!(datafeed.lastprice(&asset_xusd).is_none());
+}
+
+#[test]
+fn test_admin_transfer() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let datafeed = create_datafeed_contract(&e);
+    let admin = datafeed.admin();
+    let mistyped = Address::generate(&e);
+    let new_admin = Address::generate(&e);
+
+    let result = datafeed.try_accept_admin();
+    assert_eq!(result.unwrap_err().unwrap(), Error::NoPendingAdmin.into());
+
+    // A proposal can be replaced before it is accepted
+    datafeed.propose_admin(&mistyped);
+    assert_eq!(e.auths()[0].0, admin);
+    datafeed.propose_admin(&new_admin);
+    assert_eq!(datafeed.pending_admin(), Some(new_admin.clone()));
+    assert_eq!(datafeed.admin(), admin);
+
+    // Accepting must be signed by the proposed admin
+    datafeed.accept_admin();
+    assert_eq!(e.auths()[0].0, new_admin);
+    assert_eq!(datafeed.admin(), new_admin);
+    assert_eq!(datafeed.pending_admin(), None);
+
+    // Admin functions now require the new admin
+    datafeed.set_retention(&10);
+    assert_eq!(e.auths()[0].0, new_admin);
+}
+
+fn sign_price(
+    e: &Env,
+    key: &SigningKey,
+    feed: &Address,
+    asset: &Asset,
+    price: i128,
+    timestamp: u64,
+) -> BytesN<64> {
+    let message = (feed.clone(), asset.clone(), price, timestamp).to_xdr(e);
+    let mut buffer = [0u8; 256];
+    let bytes = &mut buffer[..message.len() as usize];
+    message.copy_into_slice(bytes);
+    BytesN::from_array(e, &key.sign(bytes).to_bytes())
+}
+
+#[test]
+fn test_signed_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(1_200);
+    let key = SigningKey::from_bytes(&[7; 32]);
+    let signer = BytesN::from_array(&e, &key.verifying_key().to_bytes());
+
+    // Only registered keys are accepted
+    let signature = sign_price(&e, &key, &datafeed.address, &asset_xlm, 10_000_000, 1_200);
+    let result =
+        datafeed.try_submit_signed_price(&signer, &asset_xlm, &10_000_000, &1_200, &signature);
+    assert_eq!(result.unwrap_err().unwrap(), Error::NotSigner.into());
+
+    datafeed.add_signers(&Vec::from_array(&e, [signer.clone()]));
+    assert_eq!(datafeed.signers(), Vec::from_array(&e, [signer.clone()]));
+    let result = datafeed.try_add_signers(&Vec::from_array(&e, [signer.clone()]));
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::SignerAlreadyExists.into()
+    );
+
+    // Anyone can relay a signed price, no authorization needed
+    e.set_auths(&[]);
+    datafeed.submit_signed_price(&signer, &asset_xlm, &10_000_000, &1_200, &signature);
+    assert!(e.auths().is_empty());
+    let last_price = datafeed.lastprice(&asset_xlm).unwrap();
+    assert_eq!(
+        (last_price.price, last_price.timestamp),
+        (10_000_000, 1_200)
+    );
+
+    // A payload can't be replayed or swapped for another one at the same timestamp
+    let result =
+        datafeed.try_submit_signed_price(&signer, &asset_xlm, &10_000_000, &1_200, &signature);
+    assert_eq!(result.unwrap_err().unwrap(), Error::RoundFinalized.into());
+
+    // Tampered payloads are rejected
+    e.ledger().set_timestamp(1_500);
+    let signature = sign_price(&e, &key, &datafeed.address, &asset_xlm, 11_000_000, 1_500);
+    let result =
+        datafeed.try_submit_signed_price(&signer, &asset_xlm, &12_000_000, &1_500, &signature);
+    assert!(result.is_err());
+    datafeed.submit_signed_price(&signer, &asset_xlm, &11_000_000, &1_500, &signature);
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 11_000_000);
+
+    e.mock_all_auths();
+    datafeed.remove_signers(&Vec::from_array(&e, [signer.clone()]));
+    assert!(datafeed.signers().is_empty());
+}
+
+#[test]
+fn test_derived_assets() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let asset_xeur: Asset = Asset::Other(Symbol::new(&e, "XEUR"));
+    let asset_eur_xlm: Asset = Asset::Other(Symbol::new(&e, "EURXLM"));
+    let asset_basket: Asset = Asset::Other(Symbol::new(&e, "BASKET"));
+    let datafeed = create_datafeed_contract(&e);
+    datafeed.add_assets(&Vec::from_array(&e, [asset_xeur.clone()]));
+    let one: i128 = 100_000_000_000_000;
+
+    // Formulas may only read reported assets already in the feed
+    let result = datafeed.try_add_derived_asset(
+        &asset_eur_xlm,
+        &Formula::Cross(asset_xeur.clone(), Asset::Other(Symbol::new(&e, "XAU"))),
+    );
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidFormula.into());
+    let result = datafeed.try_add_derived_asset(&asset_basket, &Formula::Basket(vec![&e]));
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidFormula.into());
+
+    let cross = Formula::Cross(asset_xeur.clone(), asset_xlm.clone());
+    datafeed.add_derived_asset(&asset_eur_xlm, &cross);
+    datafeed.add_derived_asset(
+        &asset_basket,
+        &Formula::Basket(vec![
+            &e,
+            (asset_xlm.clone(), one / 2),
+            (asset_xeur.clone(), one / 2),
+        ]),
+    );
+    assert_eq!(datafeed.formula(&asset_eur_xlm), Some(cross.clone()));
+    assert!(datafeed.assets().contains(&asset_eur_xlm));
+    let result = datafeed.try_add_derived_asset(
+        &Asset::Other(Symbol::new(&e, "NESTED")),
+        &Formula::Cross(asset_eur_xlm.clone(), asset_xlm.clone()),
+    );
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidFormula.into());
+    assert!(datafeed.lastprice(&asset_eur_xlm).is_none());
+
+    e.ledger().set_timestamp(600);
+    datafeed.set_asset_price(&asset_xlm, &(one / 10), &300);
+    datafeed.set_asset_price(&asset_xeur, &(one * 11 / 10), &300);
+    datafeed.set_asset_price(&asset_xlm, &(one / 5), &600);
+
+    // Derived prices are as fresh as their stalest input
+    let last_price = datafeed.lastprice(&asset_eur_xlm).unwrap();
+    assert_eq!(
+        (last_price.price, last_price.timestamp),
+        (one * 11 / 2, 300)
+    );
+    assert_eq!(
+        datafeed.lastprice(&asset_basket).unwrap().price,
+        one * 65 / 100
+    );
+    assert_eq!(
+        datafeed.price(&asset_eur_xlm, &450).unwrap().price,
+        one * 11
+    );
+
+    // One record per change to any input, newest first
+    let prices = datafeed.prices(&asset_eur_xlm, &5).unwrap();
+    assert_eq!(prices.len(), 2);
+    assert_eq!(prices.get(0).unwrap().price, one * 11 / 2);
+    assert_eq!(prices.get(1).unwrap().price, one * 11);
+
+    // Derived assets have no prices of their own
+    let result = datafeed.try_set_asset_price(&asset_eur_xlm, &one, &600);
+    assert_eq!(result.unwrap_err().unwrap(), Error::DerivedAsset.into());
+
+    // Deprecated assets can't be redefined as derived assets, nor read by a formula
+    datafeed.deprecate_asset(&asset_basket);
+    let result = datafeed.try_add_derived_asset(&asset_basket, &cross);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetDeprecated.into());
+
+    // Components can't be deprecated or removed while a formula, even a deprecated one,
+    // reads them
+    let result = datafeed.try_deprecate_asset(&asset_xeur);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetInUse.into());
+    let result = datafeed.try_remove_asset(&asset_xlm, &false);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetInUse.into());
+    datafeed.remove_asset(&asset_eur_xlm, &false);
+    assert_eq!(datafeed.formula(&asset_eur_xlm), None);
+    let result = datafeed.try_deprecate_asset(&asset_xeur);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetInUse.into());
+    datafeed.remove_asset(&asset_basket, &false);
+
+    datafeed.deprecate_asset(&asset_xeur);
+    let result = datafeed.try_add_derived_asset(
+        &Asset::Other(Symbol::new(&e, "EURXLM2")),
+        &Formula::Cross(asset_xeur.clone(), asset_xlm.clone()),
+    );
+    assert_eq!(result.unwrap_err().unwrap(), Error::AssetDeprecated.into());
+    datafeed.remove_asset(&asset_xlm, &false);
+}
+
+#[test]
+fn test_ttl_and_pruning() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    e.ledger().set_timestamp(1_500);
+    for (price, timestamp) in [(10, 300u64), (11, 600), (12, 900), (13, 1_200), (14, 1_500)] {
+        datafeed.set_asset_price(&asset_xlm, &price, &timestamp);
+    }
+    let record_ttl = |index: u64| {
+        e.as_contract(&datafeed.address, || {
+            e.storage()
+                .persistent()
+                .get_ttl(&DataKey::Record(asset_xlm.clone(), index))
+        })
+    };
+
+    // Writes keep records alive for about 30 days
+    assert!(record_ttl(0) >= 29 * 17_280);
+
+    // Bumping extends only the requested number of recent records
+    e.ledger().with_mut(|li| li.sequence_number += 2 * 17_280);
+    let stale_ttl = record_ttl(0);
+    datafeed.bump(&asset_xlm, &2);
+    assert_eq!(record_ttl(0), stale_ttl);
+    assert_eq!(record_ttl(3), 30 * 17_280);
+    assert_eq!(record_ttl(4), 30 * 17_280);
+
+    // Derived assets have no history of their own to bump
+    let asset_double = Asset::Other(Symbol::new(&e, "DOUBLE"));
+    datafeed.add_derived_asset(
+        &asset_double,
+        &Formula::Basket(vec![&e, (asset_xlm.clone(), 200_000_000_000_000)]),
+    );
+    datafeed.bump(&asset_double, &2);
+
+    // Pruning drops records before the timestamp, but only outside the retention window
+    datafeed.set_retention(&2);
+    let result = datafeed.try_prune(&asset_xlm, &u64::MAX);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::PruneWithinRetention.into()
+    );
+    let result = datafeed.try_prune(&asset_xlm, &1_200);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::PruneWithinRetention.into()
+    );
+    assert_eq!(datafeed.prune(&asset_xlm, &900), 2);
+    assert_eq!(datafeed.prune(&asset_xlm, &900), 0);
+    assert!(datafeed.price(&asset_xlm, &600).is_none());
+    assert_eq!(datafeed.prices(&asset_xlm, &10).unwrap().len(), 3);
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 14);
+    assert_eq!(datafeed.prune(&asset_double, &0), 0);
+}
+
+#[test]
+fn test_confidence() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    let reporters: std::vec::Vec<Address> = (0..3).map(|_| Address::generate(&e)).collect();
+    datafeed.add_reporters(&Vec::from_slice(&e, &reporters));
+    datafeed.set_quorum(&3);
+    e.ledger().set_timestamp(900);
+
+    // A finalized round's interval covers every submission
+    for (reporter, price) in reporters.iter().zip([100, 130, 110]) {
+        datafeed.submit_price(reporter, &asset_xlm, &price, &300);
+    }
+    let record = datafeed.lastprice_confidence(&asset_xlm).unwrap();
+    assert_eq!(
+        (record.price, record.confidence, record.timestamp),
+        (110, 20, 300)
+    );
+    assert_eq!((record.lower(), record.upper()), (90, 130));
+
+    let result = datafeed.try_set_asset_price_with_confidence(&asset_xlm, &120, &-1, &600);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::InvalidConfidence.into()
+    );
+    datafeed.set_asset_price_with_confidence(&asset_xlm, &120, &5, &600);
+    assert_eq!(
+        datafeed
+            .lastprice_confidence(&asset_xlm)
+            .unwrap()
+            .confidence,
+        5
+    );
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 120);
+
+    // Prices recorded without a confidence have none
+    datafeed.set_asset_price(&asset_xlm, &121, &900);
+    let record = datafeed.lastprice_confidence(&asset_xlm).unwrap();
+    assert_eq!((record.price, record.confidence), (121, 0));
+}
+
+#[test]
+fn test_delayed_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    assert_eq!(datafeed.price_delay(), 0);
+    datafeed.set_price_delay(&3_600);
+    assert_eq!(datafeed.price_delay(), 3_600);
+
+    e.ledger().set_timestamp(3_600);
+    datafeed.set_asset_price(&asset_xlm, &10, &3_600);
+    assert!(datafeed.current_price(&asset_xlm).is_none());
+    assert_eq!(datafeed.next_price(&asset_xlm).unwrap().price, 10);
+
+    // A new price only becomes current once the delay has passed
+    e.ledger().set_timestamp(7_200);
+    datafeed.set_asset_price(&asset_xlm, &20, &7_200);
+    assert_eq!(datafeed.current_price(&asset_xlm).unwrap().price, 10);
+    assert_eq!(datafeed.next_price(&asset_xlm).unwrap().price, 20);
+
+    e.ledger().set_timestamp(10_800);
+    assert_eq!(datafeed.current_price(&asset_xlm).unwrap().price, 20);
+
+    datafeed.set_price_delay(&0);
+    datafeed.set_asset_price(&asset_xlm, &30, &10_800);
+    assert_eq!(datafeed.current_price(&asset_xlm).unwrap().price, 30);
+}
+
+#[test]
+fn test_reporter_staking() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let asset_xlm: Asset = Asset::Other(Symbol::new(&e, "XLM"));
+    let datafeed = create_datafeed_contract(&e);
+    let sac = e.register_stellar_asset_contract_v2(Address::generate(&e));
+    let xlm = token::Client::new(&e, &sac.address());
+    let xlm_admin = token::StellarAssetClient::new(&e, &sac.address());
+    let reporters: std::vec::Vec<Address> = (0..3).map(|_| Address::generate(&e)).collect();
+    for reporter in reporters.iter() {
+        xlm_admin.mint(reporter, &1_000);
+    }
+
+    let result = datafeed.try_bond(&reporters[0], &1_000);
+    assert_eq!(result.unwrap_err().unwrap(), Error::StakingDisabled.into());
+    // Unbonding must outlast an open round
+    let result = datafeed.try_set_staking(&StakingConfig {
+        token: sac.address(),
+        min_bond: 1_000,
+        tolerance: 100,
+        slash: 5_000,
+        reward: 10,
+        unbonding_period: 719,
+    });
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::InvalidStakingConfig.into()
+    );
+    // A single reporter can't be trusted to finalize rounds alone
+    let config = StakingConfig {
+        token: sac.address(),
+        min_bond: 1_000,
+        tolerance: 100,
+        slash: 5_000,
+        reward: 10,
+        unbonding_period: 720,
+    };
+    let result = datafeed.try_set_staking(&config);
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidQuorum.into());
+    datafeed.add_reporters(&vec![&e, reporters[0].clone(), reporters[1].clone()]);
+    datafeed.set_quorum(&2);
+    datafeed.set_staking(&config);
+    let result = datafeed.try_set_quorum(&1);
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidQuorum.into());
+    let result = datafeed.try_bond(&reporters[0], &999);
+    assert_eq!(result.unwrap_err().unwrap(), Error::InsufficientBond.into());
+
+    // Bonding joins the whitelist, and reporters the admin already whitelisted can bond too
+    for reporter in reporters.iter() {
+        datafeed.bond(reporter, &1_000);
+        assert_eq!(xlm.balance(reporter), 0);
+    }
+    assert_eq!(datafeed.reporters().len(), 3);
+    datafeed.set_quorum(&3);
+    let funder = Address::generate(&e);
+    xlm_admin.mint(&funder, &100);
+    datafeed.fund_rewards(&funder, &100);
+
+    // The outlier is slashed below the minimum and removed; the others are rewarded
+    e.ledger().set_timestamp(300);
+    for (reporter, price) in reporters.iter().zip([100, 100, 150]) {
+        datafeed.submit_price(reporter, &asset_xlm, &price, &300);
+    }
+    assert_eq!(datafeed.lastprice(&asset_xlm).unwrap().price, 100);
+    assert_eq!(
+        datafeed.bond_of(&reporters[0]),
+        Some(Bond {
+            amount: 1_000,
+            rewards: 10,
+            unlocks_at: 0,
+        })
+    );
+    assert_eq!(
+        datafeed.bond_of(&reporters[2]),
+        Some(Bond {
+            amount: 500,
+            rewards: 0,
+            unlocks_at: 0,
+        })
+    );
+    assert!(!datafeed.reporters().contains(&reporters[2]));
+    assert_eq!(datafeed.reward_pool(), 580);
+
+    assert_eq!(datafeed.claim_rewards(&reporters[0]), 10);
+    assert_eq!(xlm.balance(&reporters[0]), 10);
+
+    // Unbonding returns what is left once the unbonding period is over
+    datafeed.unbond(&reporters[2]);
+    let result = datafeed.try_unbond(&reporters[2]);
+    assert_eq!(result.unwrap_err().unwrap(), Error::AlreadyUnbonding.into());
+    let result = datafeed.try_withdraw_bond(&reporters[2]);
+    assert_eq!(result.unwrap_err().unwrap(), Error::BondLocked.into());
+    e.ledger().with_mut(|li| li.sequence_number += 720);
+    datafeed.withdraw_bond(&reporters[2]);
+    assert_eq!(xlm.balance(&reporters[2]), 500);
+    assert_eq!(datafeed.bond_of(&reporters[2]), None);
+
+    // A reporter that unbonds with a submission in an open round is still slashed for it
+    let newcomer = Address::generate(&e);
+    xlm_admin.mint(&newcomer, &1_000);
+    datafeed.bond(&newcomer, &1_000);
+    datafeed.set_quorum(&2);
+    e.ledger().set_timestamp(600);
+    datafeed.submit_price(&reporters[1], &asset_xlm, &150, &600);
+    datafeed.unbond(&reporters[1]);
+    datafeed.submit_price(&reporters[0], &asset_xlm, &100, &600);
+    datafeed.submit_price(&newcomer, &asset_xlm, &100, &600);
+    assert_eq!(datafeed.bond_of(&reporters[1]).unwrap().amount, 500);
+    let result = datafeed.try_withdraw_bond(&reporters[0]);
+    assert_eq!(result.unwrap_err().unwrap(), Error::NotUnbonding.into());
+    e.ledger().with_mut(|li| li.sequence_number += 720);
+    datafeed.withdraw_bond(&reporters[1]);
+    assert_eq!(xlm.balance(&reporters[1]), 510);
+
+    // Unbonding can't drop the whitelist below quorum
+    let result = datafeed.try_unbond(&reporters[0]);
+    assert_eq!(result.unwrap_err().unwrap(), Error::InvalidQuorum.into());
+
+    // Reporters the admin removes can't bond their way back until the admin adds them again
+    let intruder = Address::generate(&e);
+    xlm_admin.mint(&intruder, &1_001);
+    datafeed.bond(&intruder, &1_000);
+    datafeed.remove_reporters(&vec![&e, intruder.clone()]);
+    let result = datafeed.try_bond(&intruder, &1);
+    assert_eq!(result.unwrap_err().unwrap(), Error::ReporterBanned.into());
+    assert!(!datafeed.reporters().contains(&intruder));
+    datafeed.add_reporters(&vec![&e, intruder.clone()]);
+    datafeed.bond(&intruder, &1);
+    assert_eq!(datafeed.bond_of(&intruder).unwrap().amount, 1_001);
+}
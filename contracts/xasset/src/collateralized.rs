@@ -33,6 +33,46 @@ pub struct CDPContract {
     pub last_interest_time: u64,
 }
 
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// How prices are read from an oracle contract
+pub enum OracleAdapter {
+    /// Our data-feed contract, looking assets up as `Asset::Other(symbol)`: `XLM` for the XLM
+    /// feed and `pegged_asset` for the asset feed. Confidence intervals and delayed prices are
+    /// only available through this adapter.
+    DataFeed,
+    /// A Reflector-compatible SEP-40 oracle, looking the asset up as `Asset::Stellar(address)`
+    Sep40Stellar(Address),
+    /// A fixed price with the given number of decimals, without reading the oracle contract.
+    /// Intended for testing.
+    Fixed(i128, u32),
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// What liquidations do when the oracle's prices are older than the maximum price age. Every
+/// other price read, including freezing a CDP, fails while prices are stale.
+pub enum StalePricePolicy {
+    /// Liquidations fail until prices are fresh again
+    Halt,
+    /// CDPs frozen before prices went stale may still be liquidated at the last known prices,
+    /// so the Stability Pool can keep absorbing bad debt during an oracle outage
+    LiquidateFrozen,
+}
+
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Which prices a CDP must be below the MCR at to be frozen or liquidated. Borrowing and
+/// withdrawing always use the spot price.
+pub enum InsolvencyMode {
+    /// Below the MCR at the spot price
+    Spot,
+    /// Below the MCR both at the spot price and at the time-weighted average of the given
+    /// number of the oracle's most recent prices, so a single bad price can't freeze or
+    /// liquidate a CDP
+    SpotAndTwap(u32),
+}
+
 // TODO was a subcontract
 /// Interface-only subcontract for a contract that implements an asset which can have
 /// Collateralized Debt Positions taken out against it.
@@ -49,6 +89,31 @@ pub trait IsCollateralized {
     /// Which asset from Oracle this tracks. For `--asset '{"Other":"USD"}'` on asset contract, set to `USD`
     fn pegged_asset(env: &Env) -> Symbol;
 
+    /// How prices are read from the XLM oracle contract
+    fn xlm_adapter(env: &Env) -> OracleAdapter;
+
+    /// How prices are read from the pegged asset oracle contract
+    fn asset_adapter(env: &Env) -> OracleAdapter;
+
+    /// Oracle read for XLM prices when the primary is stale or fails, if any
+    fn xlm_fallback(env: &Env) -> Option<Address>;
+
+    /// Oracle read for pegged asset prices when the primary is stale or fails, if any
+    fn asset_fallback(env: &Env) -> Option<Address>;
+
+    /// Basis points the primary and fallback oracles may disagree by before borrowing and
+    /// withdrawing halt. Default: 5%
+    fn max_oracle_deviation(env: &Env) -> u32;
+
+    /// Which prices a CDP must be below the MCR at to be frozen or liquidated
+    fn insolvency_mode(env: &Env) -> InsolvencyMode;
+
+    /// Maximum age of an oracle price in seconds before reads are rejected; 0 if unlimited
+    fn max_price_age(env: &Env) -> u64;
+
+    /// What liquidations do when oracle prices are stale
+    fn stale_price_policy(env: &Env) -> StalePricePolicy;
+
     /// Basis points. Default: 110%
     fn minimum_collateralization_ratio(env: &Env) -> u32;
 
@@ -73,7 +138,8 @@ pub trait IsCollateralized {
         asset_lent: i128,
     ) -> Result<(), Error>;
 
-    /// Retrieves the CDP information for a specific lender
+    /// Retrieves the CDP information for a specific lender. The CDP is valued at the oracle's
+    /// delayed prices, which are the ones it can be frozen and liquidated at.
     fn cdp(env: &Env, lender: Address) -> Result<CDPContract, Error>;
 
     /// Freeze a CDP if its Collateralization Ratio (CR) is below the xAsset's Minimum Collateralization Ratio (MCR).
@@ -166,6 +232,31 @@ pub trait IsCDPAdmin {
     /// Set the asset the xAsset is pegged to. Only callable by admin.
     fn set_pegged_asset(env: &Env, to: Symbol);
 
+    /// Set how prices are read from the XLM oracle contract. Only callable by admin.
+    fn set_xlm_adapter(env: &Env, to: OracleAdapter);
+
+    /// Set how prices are read from the pegged asset oracle contract. Only callable by admin.
+    fn set_asset_adapter(env: &Env, to: OracleAdapter);
+
+    /// Set the fallback oracle for XLM prices, read with the XLM adapter. Only callable by admin.
+    fn set_xlm_fallback(env: &Env, to: Option<Address>);
+
+    /// Set the fallback oracle for pegged asset prices, read with the pegged asset adapter. Only
+    /// callable by admin.
+    fn set_asset_fallback(env: &Env, to: Option<Address>);
+
+    /// Set the basis points the primary and fallback oracles may disagree by. Only callable by admin.
+    fn set_max_oracle_deviation(env: &Env, to: u32);
+
+    /// Set which prices a CDP must be below the MCR at to be frozen or liquidated. Only callable by admin.
+    fn set_insolvency_mode(env: &Env, to: InsolvencyMode);
+
+    /// Set the maximum age of an oracle price in seconds; 0 disables the check. Only callable by admin.
+    fn set_max_price_age(env: &Env, to: u64);
+
+    /// Set what liquidations do when oracle prices are stale. Only callable by admin.
+    fn set_stale_price_policy(env: &Env, to: StalePricePolicy);
+
     /// Set minimum collateralization ration. Only callable by admin.
     fn set_min_collat_ratio(env: &Env, to: u32) -> u32;
 
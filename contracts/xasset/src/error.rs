@@ -99,4 +99,25 @@ pub enum Error {
 
     /// Cannot transfer to self
     CannotTransferToSelf = 32,
+
+    /// The Oracle has deprecated the asset and no longer updates its price
+    OracleAssetDeprecated = 33,
+
+    /// The Oracle's most recent price is older than the maximum price age
+    OraclePriceStale = 34,
+
+    /// The primary and fallback oracles disagree by more than the maximum deviation
+    OraclePriceDeviation = 35,
+
+    /// Compliance mode is on and the address has not been authorized by the admin
+    Unauthorized = 36,
+
+    /// The xAsset's supply is already tracked and needs no backfill
+    SupplyAlreadyTracked = 37,
+
+    /// The xAsset's supply isn't tracked until it is backfilled
+    SupplyNotTracked = 38,
+
+    /// Backfilled balances exceed the backfilled CDP debt, so some lenders are missing
+    SupplyExceedsDebt = 39,
 }
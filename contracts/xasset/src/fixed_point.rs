@@ -0,0 +1,112 @@
+use crate::Error;
+
+/// How to round a quotient that isn't a whole number
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Rounding {
+    /// Towards negative infinity
+    Floor,
+    /// Towards positive infinity
+    Ceil,
+    /// To the nearest whole number, and halfway values to the nearest even one
+    Bankers,
+}
+
+/// `a * b`, or `ArithmeticError` on overflow
+pub fn mul(a: i128, b: i128) -> Result<i128, Error> {
+    a.checked_mul(b).ok_or(Error::ArithmeticError)
+}
+
+/// `a + b`, or `ArithmeticError` on overflow
+pub fn add(a: i128, b: i128) -> Result<i128, Error> {
+    a.checked_add(b).ok_or(Error::ArithmeticError)
+}
+
+/// `a - b`, or `ArithmeticError` on overflow
+pub fn sub(a: i128, b: i128) -> Result<i128, Error> {
+    a.checked_sub(b).ok_or(Error::ArithmeticError)
+}
+
+/// `10^exponent`, or `ArithmeticError` if it doesn't fit an i128
+pub fn pow10(exponent: u32) -> Result<i128, Error> {
+    10i128.checked_pow(exponent).ok_or(Error::ArithmeticError)
+}
+
+/// `numerator / denominator`, rounded as requested. Fails with `ArithmeticError` on a zero
+/// denominator or overflow.
+pub fn div(numerator: i128, denominator: i128, rounding: Rounding) -> Result<i128, Error> {
+    let quotient = numerator
+        .checked_div(denominator)
+        .ok_or(Error::ArithmeticError)?;
+    let remainder = numerator
+        .checked_rem(denominator)
+        .ok_or(Error::ArithmeticError)?;
+    if remainder == 0 {
+        return Ok(quotient);
+    }
+    // The exact quotient lies between the truncated one and the next whole number in this direction
+    let step = if (remainder < 0) == (denominator < 0) {
+        1
+    } else {
+        -1
+    };
+    let round_away = match rounding {
+        Rounding::Floor => step < 0,
+        Rounding::Ceil => step > 0,
+        Rounding::Bankers => {
+            // |remainder| < |denominator| <= 2^127, so doubling fits in a u128
+            let twice_remainder = remainder.unsigned_abs() * 2;
+            let denominator = denominator.unsigned_abs();
+            twice_remainder > denominator || (twice_remainder == denominator && quotient % 2 != 0)
+        }
+    };
+    if round_away {
+        add(quotient, step)
+    } else {
+        Ok(quotient)
+    }
+}
+
+/// `value * numerator / denominator`, rounded as requested
+pub fn mul_div(
+    value: i128,
+    numerator: i128,
+    denominator: i128,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    div(mul(value, numerator)?, denominator, rounding)
+}
+
+/// Powers of ten to multiply into a numerator and a denominator respectively to bring a value
+/// with `from` decimals to `to` decimals. Whichever decimals are larger, one of the two is 1, so
+/// no precision is lost to an intermediate division.
+pub fn decimal_factors(from: u32, to: u32) -> Result<(i128, i128), Error> {
+    if to >= from {
+        Ok((pow10(to - from)?, 1))
+    } else {
+        Ok((1, pow10(from - to)?))
+    }
+}
+
+/// Express a value with `from` decimals in `to` decimals
+pub fn rescale(value: i128, from: u32, to: u32, rounding: Rounding) -> Result<i128, Error> {
+    let (numerator, denominator) = decimal_factors(from, to)?;
+    mul_div(value, numerator, denominator, rounding)
+}
+
+/// Value of `amount` of one asset in another, given each asset's oracle price and the decimals
+/// those prices are expressed in
+pub fn convert(
+    amount: i128,
+    from_price: i128,
+    from_decimals: u32,
+    to_price: i128,
+    to_decimals: u32,
+    rounding: Rounding,
+) -> Result<i128, Error> {
+    let (numerator, denominator) = decimal_factors(from_decimals, to_decimals)?;
+    div(
+        mul(mul(amount, from_price)?, numerator)?,
+        mul(to_price, denominator)?,
+        rounding,
+    )
+}
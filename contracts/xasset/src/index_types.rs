@@ -53,3 +53,62 @@ pub struct BurnXasset {
     pub from: Address,
     pub amount: i128,
 }
+
+// SEP-41 token events, in the shapes wallets and explorers expect from any token
+
+#[contractevent(topics = ["transfer"], data_format = "single-value")]
+pub struct Transfer {
+    #[topic]
+    pub from: Address,
+    #[topic]
+    pub to: Address,
+    pub amount: i128,
+}
+
+/// A transfer to a muxed address, carrying its multiplexing id alongside the amount
+#[contractevent(topics = ["transfer"], data_format = "map")]
+pub struct TransferMuxed {
+    #[topic]
+    pub from: Address,
+    #[topic]
+    pub to: Address,
+    pub to_muxed_id: u64,
+    pub amount: i128,
+}
+
+#[contractevent(topics = ["approve"], data_format = "vec")]
+pub struct Approve {
+    #[topic]
+    pub from: Address,
+    #[topic]
+    pub spender: Address,
+    pub amount: i128,
+    pub expiration_ledger: u32,
+}
+
+#[contractevent(topics = ["mint"], data_format = "single-value")]
+pub struct Mint {
+    #[topic]
+    pub to: Address,
+    pub amount: i128,
+}
+
+#[contractevent(topics = ["burn"], data_format = "single-value")]
+pub struct Burn {
+    #[topic]
+    pub from: Address,
+    pub amount: i128,
+}
+
+#[contractevent(topics = ["clawback"], data_format = "single-value")]
+pub struct Clawback {
+    #[topic]
+    pub from: Address,
+    pub amount: i128,
+}
+
+/// The admin turned compliance mode on or off
+#[contractevent(topics = ["compliance_mode_set"], data_format = "single-value")]
+pub struct ComplianceModeSet {
+    pub enabled: bool,
+}
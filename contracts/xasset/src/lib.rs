@@ -3,6 +3,7 @@ use soroban_sdk::{self, contracttype};
 
 mod collateralized;
 mod error;
+mod fixed_point;
 mod index_types;
 mod stability_pool;
 mod storage;
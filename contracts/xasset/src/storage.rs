@@ -55,6 +55,21 @@ pub struct Interest {
     pub paid: i128,
 }
 
+/// Breakdown of an xAsset's supply, for monitoring how it is backed
+#[contracttype]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ProtocolAccounting {
+    /// xAsset minted and not yet burned
+    pub total_supply: i128,
+    /// Principal owed by all CDPs; exceeds `total_supply` once xAsset is burned or clawed back
+    /// outside of repayment
+    pub cdp_debt: i128,
+    /// xAsset held by the Stability Pool
+    pub stability_pool: i128,
+    /// xAsset held by everyone else
+    pub free_float: i128,
+}
+
 #[contracttype]
 #[derive(Clone, Copy, Default)]
 pub struct InterestDetail {
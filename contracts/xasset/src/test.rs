@@ -1,18 +1,20 @@
 #![cfg(test)]
 extern crate std;
 
-use crate::collateralized::CDPStatus;
+use crate::collateralized::{CDPStatus, InsolvencyMode, OracleAdapter, StalePricePolicy};
 use crate::data_feed;
 use crate::error::Error;
+use crate::fixed_point::{self, Rounding};
+use crate::storage::ProtocolAccounting;
 use crate::token::{TokenContract, TokenContractClient};
 use data_feed::Asset;
-use soroban_sdk::testutils::{Events, Ledger};
+use soroban_sdk::testutils::{Events, Ledger, MuxedAddress as MuxedAddressTrait};
 use soroban_sdk::{
-    Address, Env, String, Symbol, Vec,
+    Address, Env, FromVal, Map, MuxedAddress, String, Symbol, Val, Vec,
     testutils::Address as _,
     token::{self, Client as TokenClient, StellarAssetClient},
 };
-use soroban_sdk::{IntoVal, symbol_short, vec};
+use soroban_sdk::{IntoVal, contract, contractimpl, symbol_short, vec};
 
 fn create_sac_token_clients<'a>(
     e: &Env,
@@ -77,14 +79,24 @@ fn create_token_contract<'a>(
     TokenContractClient::new(e, &contract_id)
 }
 
+/// Most recent tick-aligned timestamp the data feed will accept a price for
+fn price_timestamp(e: &Env) -> u64 {
+    e.ledger().timestamp() / 300 * 300
+}
+
 fn set_token_prices(e: &Env, token: &TokenContractClient, xlm_price: i128, asset_price: i128) {
+    let timestamp = price_timestamp(e);
     let xlm_contract = token.xlm_contract();
     let client = data_feed::Client::new(e, &xlm_contract);
-    client.set_asset_price(&Asset::Other(Symbol::new(e, "XLM")), &xlm_price, &1000);
+    client.set_asset_price(&Asset::Other(Symbol::new(e, "XLM")), &xlm_price, &timestamp);
 
     let asset_contract = token.asset_contract();
     let client = data_feed::Client::new(e, &asset_contract);
-    client.set_asset_price(&Asset::Other(Symbol::new(e, "USDT")), &asset_price, &1000);
+    client.set_asset_price(
+        &Asset::Other(Symbol::new(e, "USDT")),
+        &asset_price,
+        &timestamp,
+    );
 }
 
 #[test]
@@ -126,13 +138,21 @@ fn test_cdp_operations() {
     let xlm_contract = token.xlm_contract();
     let client = data_feed::Client::new(&e, &xlm_contract);
     let xlm_price = 10_000_000_000_000;
-    client.set_asset_price(&Asset::Other(Symbol::new(&e, "XLM")), &xlm_price, &1000);
+    client.set_asset_price(
+        &Asset::Other(Symbol::new(&e, "XLM")),
+        &xlm_price,
+        &price_timestamp(&e),
+    );
 
     // Mock USDT price
     let usdt_contract = token.asset_contract();
     let client = data_feed::Client::new(&e, &usdt_contract);
     let usdt_price: i128 = 100_000_000_000_000;
-    client.set_asset_price(&Asset::Other(Symbol::new(&e, "USDT")), &usdt_price, &1000);
+    client.set_asset_price(
+        &Asset::Other(Symbol::new(&e, "USDT")),
+        &usdt_price,
+        &price_timestamp(&e),
+    );
 
     // Open CDPs
     token.open_cdp(&alice, &1_700_000_000, &100_000_000);
@@ -643,15 +663,15 @@ fn test_events_on_mint() {
     token.open_cdp(&alice, &1200_0000000, &amount);
 
     let mut events = e.events().all();
-    // Assert that three events were emitted
-    assert_eq!(events.len(), 3);
+    // Assert that four events were emitted
+    assert_eq!(events.len(), 4);
 
     // Remove the first event, which is emitted from the transfer of XLM to the contract
     events.pop_front();
     // Remove the last event, which is the custom CDP event with a map emitted
     events.pop_back();
 
-    // Verify the "mintx" event
+    // Verify the "mintx" event and the standard SEP-41 "mint" event
     assert_eq!(
         events,
         vec![
@@ -661,6 +681,956 @@ fn test_events_on_mint() {
                 (symbol_short!("mintx"), alice.clone()).into_val(&e),
                 1000_0000000i128.into_val(&e)
             ),
+            (
+                contract_id.clone(),
+                (symbol_short!("mint"), alice.clone()).into_val(&e),
+                1000_0000000i128.into_val(&e)
+            ),
         ]
     );
 }
+
+#[test]
+fn test_deprecated_oracle_asset() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let datafeed_address = datafeed.address.clone();
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+
+    data_feed::Client::new(&e, &datafeed_address)
+        .deprecate_asset(&Asset::Other(Symbol::new(&e, "USDT")));
+    assert_eq!(
+        token.try_lastprice_asset().err().unwrap().unwrap(),
+        Error::OracleAssetDeprecated
+    );
+    let result = token.try_open_cdp(&alice, &2_000_000_000, &100_000_000);
+    assert_eq!(result.unwrap_err().unwrap(), Error::OracleAssetDeprecated);
+}
+
+#[test]
+fn test_confidence_bounds_collateralization() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let datafeed_address = datafeed.address.clone();
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    let datafeed = data_feed::Client::new(&e, &datafeed_address);
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+
+    // 200% at the spot price, but only 100% at the bottom of XLM's interval
+    let timestamp = price_timestamp(&e);
+    datafeed.set_asset_price_with_confidence(
+        &Asset::Other(Symbol::new(&e, "XLM")),
+        &10_000_000_000_000,
+        &5_000_000_000_000,
+        &timestamp,
+    );
+    datafeed.set_asset_price(
+        &Asset::Other(Symbol::new(&e, "USDT")),
+        &100_000_000_000_000,
+        &timestamp,
+    );
+    let result = token.try_open_cdp(&alice, &2_000_000_000, &100_000_000);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::InsufficientCollateralization
+    );
+
+    // A tighter interval clears the minimum
+    datafeed.set_asset_price_with_confidence(
+        &Asset::Other(Symbol::new(&e, "XLM")),
+        &10_000_000_000_000,
+        &1_000_000_000_000,
+        &timestamp,
+    );
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+
+    // The position itself is valued at the price it could be frozen at
+    assert_eq!(token.cdp(&alice).collateralization_ratio, 20_000);
+}
+
+#[test]
+fn test_delayed_oracle_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let datafeed_address = datafeed.address.clone();
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    let datafeed = data_feed::Client::new(&e, &datafeed_address);
+    datafeed.set_price_delay(&3_600);
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+
+    // No price has cleared the delay yet
+    Ledger::set_timestamp(&e.ledger(), 3_600);
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+    let result = token.try_open_cdp(&alice, &2_000_000_000, &100_000_000);
+    assert_eq!(result.unwrap_err().unwrap(), Error::OraclePriceFetchFailed);
+
+    Ledger::set_timestamp(&e.ledger(), 7_200);
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+
+    // XLM halves: borrowing sees the new price at once, but the CDP can't be frozen until
+    // the price clears the delay
+    datafeed.set_asset_price(
+        &Asset::Other(Symbol::new(&e, "XLM")),
+        &5_000_000_000_000,
+        &7_200,
+    );
+    assert_eq!(token.cdp(&alice).collateralization_ratio, 20_000);
+    let result = token.try_borrow_xasset(&alice, &1);
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        Error::InsufficientCollateralization
+    );
+    let result = token.try_freeze_cdp(&alice);
+    assert_eq!(result.unwrap_err().unwrap(), Error::CDPNotInsolvent);
+
+    Ledger::set_timestamp(&e.ledger(), 10_800);
+    assert!(token.cdp(&alice).collateralization_ratio < 11_000);
+    token.freeze_cdp(&alice);
+}
+
+#[test]
+fn test_oracle_adapters() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    assert_eq!(token.xlm_adapter(), OracleAdapter::DataFeed);
+
+    // Fixed prices don't touch the oracle, which has no prices yet
+    assert_eq!(
+        token.try_lastprice_xlm().err().unwrap().unwrap(),
+        Error::OraclePriceFetchFailed
+    );
+    token.set_xlm_adapter(&OracleAdapter::Fixed(1_000_000, 7));
+    token.set_asset_adapter(&OracleAdapter::Fixed(10_000_000, 7));
+    assert_eq!(token.lastprice_xlm().price, 1_000_000);
+    assert_eq!(token.decimals_xlm_feed(), 7);
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000);
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+    assert_eq!(token.cdp(&alice).collateralization_ratio, 20_000);
+
+    // A SEP-40 oracle quoting XLM by its Stellar asset contract
+    let sep40 = data_feed::Client::new(
+        &e,
+        &e.register(
+            data_feed::WASM,
+            (
+                Address::generate(&e),
+                Vec::from_array(&e, [Asset::Stellar(xlm_admin.address.clone())]),
+                Asset::Other(Symbol::new(&e, "USD")),
+                14u32,
+                300u32,
+            ),
+        ),
+    );
+    sep40.set_asset_price(
+        &Asset::Stellar(xlm_admin.address.clone()),
+        &20_000_000_000_000,
+        &price_timestamp(&e),
+    );
+    token.set_xlm_contract(&sep40.address);
+    token.set_xlm_adapter(&OracleAdapter::Sep40Stellar(xlm_admin.address.clone()));
+    assert_eq!(token.lastprice_xlm().price, 20_000_000_000_000);
+    assert_eq!(token.decimals_xlm_feed(), 14);
+}
+
+#[test]
+fn test_stale_oracle_prices() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    assert_eq!(token.max_price_age(), 0);
+    assert_eq!(token.stale_price_policy(), StalePricePolicy::Halt);
+    token.set_max_price_age(&900);
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+    let staker = Address::generate(&e);
+    xlm_admin.mint(&staker, &2_000_000_000_000);
+
+    Ledger::set_timestamp(&e.ledger(), 3_600);
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+    token.open_cdp(&staker, &100_000_0000000, &1_000_0000000);
+    token.stake(&staker, &500_0000000);
+    token.open_cdp(&alice, &10_000_000_000, &70_0000000);
+
+    // XLM halves and the CDP is frozen, then the oracle stops updating
+    Ledger::set_timestamp(&e.ledger(), 4_200);
+    set_token_prices(&e, &token, 5_000_000_000_000, 100_000_000_000_000);
+    token.freeze_cdp(&alice);
+
+    Ledger::set_timestamp(&e.ledger(), 5_400);
+    assert_eq!(
+        token.try_lastprice_xlm().err().unwrap().unwrap(),
+        Error::OraclePriceStale
+    );
+    assert_eq!(
+        token.try_borrow_xasset(&staker, &1).unwrap_err().unwrap(),
+        Error::OraclePriceStale
+    );
+    assert_eq!(
+        token.try_cdp(&staker).err().unwrap().unwrap(),
+        Error::OraclePriceStale
+    );
+    assert_eq!(
+        token.try_liquidate_cdp(&alice).unwrap_err().unwrap(),
+        Error::OraclePriceStale
+    );
+
+    // Frozen CDPs can still be liquidated at the last known prices if the admin allows it
+    token.set_stale_price_policy(&StalePricePolicy::LiquidateFrozen);
+    let (liquidated_debt, _, status) = token.liquidate_cdp(&alice);
+    assert_eq!(liquidated_debt, 70_0000000);
+    assert_eq!(status, CDPStatus::Closed);
+
+    // Fresh prices lift the halt
+    set_token_prices(&e, &token, 5_000_000_000_000, 100_000_000_000_000);
+    token.borrow_xasset(&staker, &1);
+}
+
+#[test]
+fn test_fallback_oracle() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    token.set_max_price_age(&900);
+
+    // The fallback quotes the same assets with 7 decimals rather than 14
+    let xlm = Asset::Other(Symbol::new(&e, "XLM"));
+    let usdt = Asset::Other(Symbol::new(&e, "USDT"));
+    let fallback = data_feed::Client::new(
+        &e,
+        &e.register(
+            data_feed::WASM,
+            (
+                Address::generate(&e),
+                Vec::from_array(&e, [xlm.clone(), usdt.clone()]),
+                usdt.clone(),
+                7u32,
+                300u32,
+            ),
+        ),
+    );
+    assert_eq!(token.xlm_fallback(), None);
+    assert_eq!(token.max_oracle_deviation(), 500);
+    token.set_xlm_fallback(&Some(fallback.address.clone()));
+    token.set_asset_fallback(&Some(fallback.address.clone()));
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+
+    Ledger::set_timestamp(&e.ledger(), 3_600);
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+    fallback.set_asset_price(&xlm, &1_000_000, &3_600);
+    fallback.set_asset_price(&usdt, &10_000_000, &3_600);
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+    token.borrow_xasset(&alice, &1);
+
+    // The oracles disagree on XLM by 20%: no borrowing or withdrawing until the admin allows it
+    fallback.set_asset_price(&xlm, &1_200_000, &3_600);
+    assert_eq!(
+        token.try_borrow_xasset(&alice, &1).unwrap_err().unwrap(),
+        Error::OraclePriceDeviation
+    );
+    assert_eq!(
+        token
+            .try_withdraw_collateral(&alice, &1)
+            .unwrap_err()
+            .unwrap(),
+        Error::OraclePriceDeviation
+    );
+    token.set_max_oracle_deviation(&3_000);
+    token.borrow_xasset(&alice, &1);
+    token.set_max_oracle_deviation(&500);
+
+    // The primary stops updating and prices are read from the fallback in its stead
+    Ledger::set_timestamp(&e.ledger(), 5_400);
+    fallback.set_asset_price(&xlm, &1_000_000, &5_400);
+    fallback.set_asset_price(&usdt, &10_000_000, &5_400);
+    assert_eq!(token.lastprice_xlm().price, 10_000_000_000_000);
+    assert_eq!(token.cdp(&alice).collateralization_ratio, 19_999);
+    token.borrow_xasset(&alice, &1);
+
+    token.set_xlm_fallback(&None);
+    assert_eq!(
+        token.try_lastprice_xlm().err().unwrap().unwrap(),
+        Error::OraclePriceStale
+    );
+}
+
+#[test]
+fn test_cached_oracle_decimals() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let token = TokenContractClient::new(
+        &e,
+        &e.register(
+            TokenContract,
+            (
+                Address::generate(&e),
+                xlm_admin.address.clone(),
+                Address::generate(&e),
+                Address::generate(&e),
+                Symbol::new(&e, "USDT"),
+                11_000u32,
+                String::from_str(&e, "United States Dollar xAsset"),
+                String::from_str(&e, "xUSD"),
+                7u32,
+                11_00u32,
+            ),
+        ),
+    );
+
+    // The oracles didn't exist when the xAsset was deployed, so nothing was cached
+    assert_eq!(
+        token.try_decimals_xlm_feed().unwrap_err().unwrap(),
+        Error::OracleDecimalsFetchFailed
+    );
+
+    // Setting the oracles caches their decimals
+    let datafeed = create_data_feed(&e);
+    token.set_xlm_contract(&datafeed.address);
+    token.set_asset_contract(&datafeed.address);
+    assert_eq!(token.decimals_xlm_feed(), 14);
+    assert_eq!(token.decimals_asset_feed(), 14);
+
+    token.set_xlm_adapter(&OracleAdapter::Fixed(1_000_000, 7));
+    assert_eq!(token.decimals_xlm_feed(), 7);
+    token.set_xlm_adapter(&OracleAdapter::DataFeed);
+    assert_eq!(token.decimals_xlm_feed(), 14);
+}
+
+#[test]
+fn test_twap_insolvency_mode() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    assert_eq!(token.insolvency_mode(), InsolvencyMode::Spot);
+    token.set_insolvency_mode(&InsolvencyMode::SpotAndTwap(4));
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+    let staker = Address::generate(&e);
+    xlm_admin.mint(&staker, &2_000_000_000_000);
+
+    for timestamp in [3_600, 3_900, 4_200] {
+        Ledger::set_timestamp(&e.ledger(), timestamp);
+        set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+    }
+    token.open_cdp(&staker, &100_000_0000000, &1_000_0000000);
+    token.stake(&staker, &500_0000000);
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+
+    // A single bad tick makes the CDP insolvent at spot, but not on average
+    Ledger::set_timestamp(&e.ledger(), 4_500);
+    set_token_prices(&e, &token, 5_000_000_000_000, 100_000_000_000_000);
+    assert_eq!(token.cdp(&alice).status, CDPStatus::Insolvent);
+    assert_eq!(
+        token.try_freeze_cdp(&alice).unwrap_err().unwrap(),
+        Error::CDPNotInsolvent
+    );
+
+    // Once the lower price persists, the average catches up
+    for timestamp in [4_800, 5_100, 5_400] {
+        Ledger::set_timestamp(&e.ledger(), timestamp);
+        set_token_prices(&e, &token, 5_000_000_000_000, 100_000_000_000_000);
+    }
+    token.freeze_cdp(&alice);
+
+    // A frozen CDP is only liquidated while it is still insolvent on average, so neither a
+    // recovery nor a later single bad tick liquidates it
+    for timestamp in [5_700, 6_000, 6_300] {
+        Ledger::set_timestamp(&e.ledger(), timestamp);
+        set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+    }
+    assert_eq!(
+        token.try_liquidate_cdp(&alice).unwrap_err().unwrap(),
+        Error::CDPNotInsolvent
+    );
+    Ledger::set_timestamp(&e.ledger(), 6_600);
+    set_token_prices(&e, &token, 5_000_000_000_000, 100_000_000_000_000);
+    assert!(token.cdp(&alice).collateralization_ratio < 11_000);
+    assert_eq!(
+        token.try_liquidate_cdp(&alice).unwrap_err().unwrap(),
+        Error::CDPNotInsolvent
+    );
+
+    for timestamp in [6_900, 7_200, 7_500] {
+        Ledger::set_timestamp(&e.ledger(), timestamp);
+        set_token_prices(&e, &token, 5_000_000_000_000, 100_000_000_000_000);
+    }
+    let (liquidated_debt, _, status) = token.liquidate_cdp(&alice);
+    assert_eq!(liquidated_debt, 100_000_000);
+    assert_eq!(status, CDPStatus::Closed);
+}
+
+#[test]
+fn test_fixed_point_math() {
+    assert_eq!(fixed_point::div(7, 2, Rounding::Floor), Ok(3));
+    assert_eq!(fixed_point::div(7, 2, Rounding::Ceil), Ok(4));
+    assert_eq!(fixed_point::div(7, 2, Rounding::Bankers), Ok(4));
+    assert_eq!(fixed_point::div(5, 2, Rounding::Bankers), Ok(2));
+    assert_eq!(fixed_point::div(-7, 2, Rounding::Floor), Ok(-4));
+    assert_eq!(fixed_point::div(-7, 2, Rounding::Ceil), Ok(-3));
+    assert_eq!(fixed_point::div(-5, 2, Rounding::Bankers), Ok(-2));
+    assert_eq!(fixed_point::div(8, 3, Rounding::Bankers), Ok(3));
+    assert_eq!(
+        fixed_point::div(1, 0, Rounding::Floor),
+        Err(Error::ArithmeticError)
+    );
+    assert_eq!(
+        fixed_point::mul_div(i128::MAX, 2, 2, Rounding::Floor),
+        Err(Error::ArithmeticError)
+    );
+
+    // Decimals in either order
+    assert_eq!(
+        fixed_point::rescale(123_456, 3, 1, Rounding::Floor),
+        Ok(1_234)
+    );
+    assert_eq!(fixed_point::rescale(123, 1, 3, Rounding::Floor), Ok(12_300));
+    // 10 xUSD at $1 (14 decimals) is 100 XLM at $0.10 (7 decimals), and back
+    assert_eq!(
+        fixed_point::convert(10, 100_000_000_000_000, 14, 1_000_000, 7, Rounding::Floor),
+        Ok(100)
+    );
+    assert_eq!(
+        fixed_point::convert(100, 1_000_000, 7, 100_000_000_000_000, 14, Rounding::Floor),
+        Ok(10)
+    );
+}
+
+#[test]
+fn test_xlm_feed_with_fewer_decimals() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    token.set_xlm_adapter(&OracleAdapter::Fixed(1_000_000, 7));
+    token.set_asset_adapter(&OracleAdapter::Fixed(100_000_000_000_000, 14));
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000);
+    Ledger::set_timestamp(&e.ledger(), 3_600);
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+    assert_eq!(token.cdp(&alice).collateralization_ratio, 20_000);
+
+    // A year's interest at 11% on 10 xUSD is 1.1 xUSD, or 11 XLM
+    Ledger::set_timestamp(&e.ledger(), 3_600 + 31_536_000);
+    let interest = token.get_accrued_interest(&alice);
+    assert_eq!(interest.amount, 11_000_000);
+    assert_eq!(interest.amount_in_xlm, 110_000_000);
+}
+
+#[test]
+fn test_sep41_events() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+
+    let alice = Address::generate(&e);
+    let bob = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000);
+    token.open_cdp(&alice, &2_000_000_000, &100_000_000);
+
+    token.transfer(&alice, &bob, &10);
+    assert_eq!(
+        vec![&e, e.events().all().last().unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("transfer"), alice.clone(), bob.clone()).into_val(&e),
+                10i128.into_val(&e)
+            )
+        ]
+    );
+
+    // Transfers to muxed accounts carry the multiplexing id
+    let muxed = <MuxedAddress as MuxedAddressTrait>::generate(&e);
+    token.transfer(&alice, &muxed, &10);
+    let mut data = Map::<Symbol, Val>::new(&e);
+    data.set(Symbol::new(&e, "amount"), 10i128.into_val(&e));
+    data.set(
+        Symbol::new(&e, "to_muxed_id"),
+        muxed.id().unwrap().into_val(&e),
+    );
+    let event = e.events().all().last().unwrap();
+    assert_eq!(
+        event.1,
+        (symbol_short!("transfer"), alice.clone(), muxed.address()).into_val(&e)
+    );
+    assert_eq!(Map::<Symbol, Val>::from_val(&e, &event.2), data);
+
+    token.approve(&alice, &bob, &5, &100);
+    assert_eq!(
+        vec![&e, e.events().all().last().unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("approve"), alice.clone(), bob.clone()).into_val(&e),
+                (vec![&e, 5i128.into_val(&e), 100u32.into_val(&e)] as Vec<Val>).into_val(&e)
+            )
+        ]
+    );
+
+    // Adjusting an allowance reports the new allowance the same way
+    let expiration_ledger = e.ledger().sequence() + 1000;
+    token.increase_allowance(&alice, &bob, &3);
+    assert_eq!(
+        vec![&e, e.events().all().last().unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("approve"), alice.clone(), bob.clone()).into_val(&e),
+                (vec![&e, 8i128.into_val(&e), expiration_ledger.into_val(&e)] as Vec<Val>)
+                    .into_val(&e)
+            )
+        ]
+    );
+    token.decrease_allowance(&alice, &bob, &2);
+    assert_eq!(
+        vec![&e, e.events().all().last().unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("approve"), alice.clone(), bob.clone()).into_val(&e),
+                (vec![&e, 6i128.into_val(&e), expiration_ledger.into_val(&e)] as Vec<Val>)
+                    .into_val(&e)
+            )
+        ]
+    );
+
+    token.burn(&bob, &5);
+    assert_eq!(
+        vec![&e, e.events().all().last().unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("burn"), bob.clone()).into_val(&e),
+                5i128.into_val(&e)
+            )
+        ]
+    );
+
+    // Clawbacks burn through the same path, but are reported as clawbacks
+    token.clawback(&bob, &5);
+    let events = e.events().all();
+    assert_eq!(
+        vec![&e, events.last().unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("clawback"), bob.clone()).into_val(&e),
+                5i128.into_val(&e)
+            )
+        ]
+    );
+    assert_eq!(
+        vec![&e, events.get(events.len() - 2).unwrap()],
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (symbol_short!("burnx"), bob.clone()).into_val(&e),
+                5i128.into_val(&e)
+            )
+        ]
+    );
+    assert_eq!(token.balance(&bob), 0);
+}
+
+#[test]
+fn test_protocol_accounting() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+    assert_eq!(token.total_supply(), 0);
+
+    let alice = Address::generate(&e);
+    let staker = Address::generate(&e);
+    let bob = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+    xlm_admin.mint(&staker, &2_000_000_000_000);
+
+    token.open_cdp(&staker, &100000_0000000, &1000_0000000);
+    token.open_cdp(&alice, &10_000_000_000, &50_0000000);
+    token.borrow_xasset(&alice, &20_0000000);
+    token.stake(&staker, &500_0000000);
+    token.transfer(&alice, &bob, &10_0000000);
+    assert_eq!(token.total_supply(), 1070_0000000);
+    assert_eq!(
+        token.protocol_accounting(),
+        ProtocolAccounting {
+            total_supply: 1070_0000000,
+            cdp_debt: 1070_0000000,
+            stability_pool: 500_0000000,
+            free_float: 570_0000000,
+        }
+    );
+
+    // Repayment burns supply and debt alike
+    token.repay_debt(&alice, &30_0000000);
+    assert_eq!(token.total_supply(), 1040_0000000);
+    assert_eq!(token.protocol_accounting().cdp_debt, 1040_0000000);
+
+    // Liquidation burns the CDP's debt out of the Stability Pool
+    set_token_prices(&e, &token, 1_000_000_000_000, 100_000_000_000_000);
+    token.freeze_cdp(&alice);
+    token.liquidate_cdp(&alice);
+    assert_eq!(
+        token.protocol_accounting(),
+        ProtocolAccounting {
+            total_supply: 1000_0000000,
+            cdp_debt: 1000_0000000,
+            stability_pool: 460_0000000,
+            free_float: 540_0000000,
+        }
+    );
+
+    // Clawed back xAsset leaves supply but the debt backing it stays owed
+    token.clawback(&bob, &10_0000000);
+    assert_eq!(
+        token.protocol_accounting(),
+        ProtocolAccounting {
+            total_supply: 990_0000000,
+            cdp_debt: 1000_0000000,
+            stability_pool: 460_0000000,
+            free_float: 530_0000000,
+        }
+    );
+}
+
+#[test]
+fn test_compliance_mode() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+
+    let alice = Address::generate(&e);
+    let bob = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+    xlm_admin.mint(&bob, &2_000_000_000_000);
+
+    // Authorization is not enforced until compliance mode is turned on
+    assert!(!token.compliance_mode());
+    token.open_cdp(&alice, &10_000_000_000, &20_0000000);
+    token.transfer(&alice, &bob, &10_0000000);
+
+    token.set_compliance_mode(&true);
+    assert_eq!(
+        e.events().all(),
+        vec![
+            &e,
+            (
+                token.address.clone(),
+                (Symbol::new(&e, "compliance_mode_set"),).into_val(&e),
+                true.into_val(&e)
+            )
+        ]
+    );
+    assert!(token.compliance_mode());
+    token.set_authorized(&alice, &true);
+
+    assert_eq!(
+        token.try_open_cdp(&bob, &10_000_000_000, &20_0000000),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    assert_eq!(
+        token.try_stake(&bob, &5_0000000),
+        Err(Ok(Error::Unauthorized.into()))
+    );
+    assert!(token.try_transfer(&bob, &alice, &1_0000000).is_err());
+    assert!(token.try_transfer(&alice, &bob, &1_0000000).is_err());
+
+    // Authorized addresses keep full use of the xAsset, including the Stability Pool
+    token.borrow_xasset(&alice, &10_0000000);
+    token.stake(&alice, &15_0000000);
+
+    token.set_authorized(&bob, &true);
+    token.transfer(&bob, &alice, &1_0000000);
+
+    // Clawback still applies to accounts whose authorization was revoked
+    token.set_authorized(&bob, &false);
+    token.clawback(&bob, &9_0000000);
+    assert_eq!(token.balance(&bob), 0);
+    assert_eq!(token.total_supply(), 21_0000000);
+}
+
+#[test]
+fn test_settings_missing_after_upgrade() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (_, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let datafeed = create_data_feed(&e);
+    let admin: Address = Address::generate(&e);
+    let token = create_token_contract(&e, admin, datafeed, xlm_admin.address.clone());
+    set_token_prices(&e, &token, 10_000_000_000_000, 100_000_000_000_000);
+
+    // A contract upgraded from the first release only has its original state stored
+    e.as_contract(&token.address, || {
+        for key in ["ORACLES", "SUPPLY", "COMPLY"] {
+            e.storage().instance().remove(&Symbol::new(&e, key));
+        }
+    });
+
+    assert_eq!(token.xlm_adapter(), OracleAdapter::DataFeed);
+    assert_eq!(token.max_oracle_deviation(), 500);
+    assert_eq!(token.insolvency_mode(), InsolvencyMode::Spot);
+    assert!(!token.compliance_mode());
+
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000);
+    token.open_cdp(&alice, &2_000_000_000, &10_0000000);
+    assert_eq!(token.cdp(&alice).asset_lent, 10_0000000);
+    token.set_max_price_age(&600);
+    assert_eq!(token.max_price_age(), 600);
+
+    // Supply isn't tracked until the admin backfills it from balances and CDPs
+    let bob = Address::generate(&e);
+    token.transfer(&alice, &bob, &4_0000000);
+    assert_eq!(
+        token.try_total_supply().unwrap_err().unwrap(),
+        Error::SupplyNotTracked
+    );
+    assert_eq!(
+        token.try_protocol_accounting().unwrap_err().unwrap(),
+        Error::SupplyNotTracked
+    );
+    // Balances beyond the listed CDPs' debt mean a lender was left out
+    let carol = Address::generate(&e);
+    xlm_admin.mint(&carol, &2_000_000_000);
+    token.open_cdp(&carol, &2_000_000_000, &10_0000000);
+    assert_eq!(
+        token
+            .try_backfill_supply(
+                &vec![&e, alice.clone(), bob.clone(), carol.clone()],
+                &vec![&e, alice.clone()],
+            )
+            .unwrap_err()
+            .unwrap(),
+        Error::SupplyExceedsDebt
+    );
+    token.transfer(&carol, &alice, &10_0000000);
+    token.backfill_supply(
+        &vec![&e, alice.clone(), bob.clone(), bob.clone()],
+        &vec![&e, alice.clone(), carol.clone()],
+    );
+    assert_eq!(
+        token.protocol_accounting(),
+        ProtocolAccounting {
+            total_supply: 20_0000000,
+            cdp_debt: 20_0000000,
+            stability_pool: 0,
+            free_float: 20_0000000,
+        }
+    );
+    token.borrow_xasset(&alice, &1_0000000);
+    assert_eq!(token.total_supply(), 21_0000000);
+    assert_eq!(
+        token
+            .try_backfill_supply(&vec![&e, alice.clone()], &vec![&e, alice.clone()])
+            .unwrap_err()
+            .unwrap(),
+        Error::SupplyAlreadyTracked
+    );
+}
+
+/// Data-feed stand-in quoting XLM at 0.1 and USDT at 1, which counts the price reads made of it
+#[contract]
+struct CountingOracle;
+
+#[contractimpl]
+impl CountingOracle {
+    pub fn decimals(_e: Env) -> u32 {
+        14
+    }
+
+    pub fn lastprice(e: Env, asset: Asset) -> Option<data_feed::PriceData> {
+        Some(data_feed::PriceData {
+            price: Self::read(&e, &asset),
+            timestamp: e.ledger().timestamp(),
+        })
+    }
+
+    pub fn lastprice_confidence(e: Env, asset: Asset) -> Option<data_feed::PriceConfidence> {
+        Some(data_feed::PriceConfidence {
+            price: Self::read(&e, &asset),
+            confidence: 0,
+            timestamp: e.ledger().timestamp(),
+        })
+    }
+
+    pub fn current_price(e: Env, asset: Asset) -> Option<data_feed::PriceData> {
+        Self::lastprice(e, asset)
+    }
+
+    pub fn set_xlm_price(e: Env, price: i128) {
+        e.storage().instance().set(&symbol_short!("XLM"), &price);
+    }
+
+    pub fn reads(e: Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&symbol_short!("READS"))
+            .unwrap_or(0)
+    }
+
+    fn read(e: &Env, asset: &Asset) -> i128 {
+        let reads = Self::reads(e.clone()) + 1;
+        e.storage().instance().set(&symbol_short!("READS"), &reads);
+        if *asset == Asset::Other(Symbol::new(e, "XLM")) {
+            e.storage()
+                .instance()
+                .get(&symbol_short!("XLM"))
+                .unwrap_or(10_000_000_000_000)
+        } else {
+            100_000_000_000_000
+        }
+    }
+}
+
+#[test]
+fn test_oracle_reads_per_invocation() {
+    let e = Env::default();
+    e.mock_all_auths();
+
+    let xlm_admin_address = Address::generate(&e);
+    let (xlm, xlm_admin) = create_sac_token_clients(&e, &xlm_admin_address);
+    let oracle = CountingOracleClient::new(&e, &e.register(CountingOracle, ()));
+    let token = TokenContractClient::new(
+        &e,
+        &e.register(
+            TokenContract,
+            (
+                Address::generate(&e),
+                xlm_admin.address.clone(),
+                oracle.address.clone(),
+                oracle.address.clone(),
+                Symbol::new(&e, "USDT"),
+                11_000u32,
+                String::from_str(&e, "United States Dollar xAsset"),
+                String::from_str(&e, "xUSD"),
+                7u32,
+                11_00u32,
+            ),
+        ),
+    );
+    let alice = Address::generate(&e);
+    xlm_admin.mint(&alice, &2_000_000_000_000);
+    let staker = Address::generate(&e);
+    xlm_admin.mint(&staker, &2_000_000_000_000);
+    Ledger::set_timestamp(&e.ledger(), 1_000);
+    token.open_cdp(&staker, &1_000_000_000_000, &1_000_000_000);
+    token.stake(&staker, &500_000_000);
+
+    // Each feed is quoted and its delayed price read once per invocation
+    let reads = oracle.reads();
+    token.open_cdp(&alice, &2_000_000_000, &50_000_000);
+    assert_eq!(oracle.reads() - reads, 4);
+    let reads = oracle.reads();
+    token.borrow_xasset(&alice, &1);
+    assert_eq!(oracle.reads() - reads, 4);
+    let reads = oracle.reads();
+    token.withdraw_collateral(&alice, &1);
+    assert_eq!(oracle.reads() - reads, 4);
+
+    // Interest is settled at the spot price, read once per feed
+    Ledger::set_timestamp(&e.ledger(), 31_537_000);
+    let reads = oracle.reads();
+    token.pay_interest(&alice, &1);
+    assert_eq!(oracle.reads() - reads, 2);
+    xlm.approve(&alice, &token.address, &1_000_000_000, &1_000);
+    let reads = oracle.reads();
+    token.repay_debt(&alice, &1);
+    assert_eq!(oracle.reads() - reads, 2);
+
+    // A fallback is quoted once more, to check the oracles agree, but only where they're
+    // compared
+    token.set_xlm_fallback(&Some(oracle.address.clone()));
+    let reads = oracle.reads();
+    token.borrow_xasset(&alice, &1);
+    assert_eq!(oracle.reads() - reads, 5);
+    oracle.set_xlm_price(&2_000_000_000_000);
+    let reads = oracle.reads();
+    token.freeze_cdp(&alice);
+    assert_eq!(oracle.reads() - reads, 4);
+    let reads = oracle.reads();
+    token.liquidate_cdp(&alice);
+    assert_eq!(oracle.reads() - reads, 4);
+}
@@ -1,18 +1,25 @@
 use core::cmp;
 
 use soroban_sdk::{
-    self, Address, BytesN, Env, MuxedAddress, String, Symbol, Vec, assert_with_error, contract,
-    contractimpl, contracttype, panic_with_error, symbol_short,
+    self, Address, BytesN, Env, Map, MuxedAddress, String, Symbol, Vec, assert_with_error,
+    contract, contractimpl, contracttype, panic_with_error, symbol_short,
     token::{TokenClient, TokenInterface},
 };
 
 use crate::{
     Error, PriceData,
-    collateralized::{CDPContract, CDPStatus, IsCDPAdmin, IsCollateralized},
+    collateralized::{
+        CDPContract, CDPStatus, InsolvencyMode, IsCDPAdmin, IsCollateralized, OracleAdapter,
+        StalePricePolicy,
+    },
     data_feed,
-    index_types::{BurnXasset, MintXasset},
+    fixed_point::{self, Rounding},
+    index_types::{
+        Approve, Burn, BurnXasset, Clawback, ComplianceModeSet, Mint, MintXasset, Transfer,
+        TransferMuxed,
+    },
     stability_pool::{AvailableAssets, IsStabilityPool, StakerPosition},
-    storage::{Allowance, CDPInternal, Interest, InterestDetail, Txn},
+    storage::{Allowance, CDPInternal, Interest, InterestDetail, ProtocolAccounting, Txn},
 };
 const VERSION_STRING: &str = concat!(
     env!("CARGO_PKG_VERSION_MAJOR"),
@@ -26,42 +33,17 @@ const PRODUCT_CONSTANT: i128 = 1_000_000_000;
 const DEPOSIT_FEE: i128 = 10_000_000;
 const STAKE_FEE: i128 = 70_000_000;
 const UNSTAKE_RETURN: i128 = 20_000_000;
+const MAX_ORACLE_DEVIATION: u32 = 500; // 5% in basis points
 // Constants for interest calculation
 const SECONDS_PER_YEAR: u64 = 31_536_000; // 365 days
-const INTEREST_PRECISION: i128 = 1_000_000_000; // 9 decimal places for precision
-const DEFAULT_PRECISION: i128 = 10_000_000; // 7 decimal places for precision
 
 fn assert_positive(env: &Env, value: i128) {
     assert_with_error!(env, value >= 0, Error::ValueNotPositive);
 }
 
-fn bankers_round(value: i128, precision: i128) -> i128 {
-    let half = precision / 2;
-
-    // Calculate remainder and halfway point
-    let remainder = value.rem_euclid(precision);
-    let halfway = precision - half; // This is effectively (precision / 2)
-
-    // Determine if we are exactly in the middle
-    let is_middle = remainder == half || remainder == halfway;
-
-    // Compute rounded value
-    if is_middle {
-        // Check if the current value is even, if so, round down; otherwise, round up
-        if (value / precision) % 2 == 0 {
-            value / precision
-        } else {
-            value / precision + 1
-        }
-    } else if remainder < half {
-        value / precision
-    } else {
-        value / precision + 1
-    }
-}
-
+/// Collateral value over debt value in basis points, net of accrued interest. Saturates at
+/// `u32::MAX` for CDPs without debt and at 0 for CDPs whose interest exceeds their collateral.
 fn calculate_collateralization_ratio(
-    env: &Env,
     asset_lent: i128,
     xasset_price: i128,
     xlm_deposited: i128,
@@ -69,35 +51,22 @@ fn calculate_collateralization_ratio(
     xlm_decimals: u32,
     xasset_decimals: u32,
     accrued_interest: i128,
-) -> u32 {
-    let (numer_decimals, denom_decimals) = if xlm_decimals == xasset_decimals {
-        (0, 0)
-    } else if xlm_decimals > xasset_decimals {
-        (0, xlm_decimals - xasset_decimals)
-    } else {
-        (xasset_decimals - xlm_decimals, 0)
-    };
-
-    let collateralization_ratio = if asset_lent == 0 || xasset_price == 0 {
-        u32::MAX
-    } else {
-        // Include accrued interest in the calculation: (a - i)b / (mp)
-        let effective_xlm = xlm_deposited.saturating_sub(accrued_interest);
-        let basis_in_xlm = BASIS_POINTS
-            .checked_mul(effective_xlm)
-            .unwrap_or_else(|| panic_with_error!(env, Error::ArithmeticError));
-        (basis_in_xlm
-            .checked_mul(xlm_price)
-            .unwrap_or_else(|| panic_with_error!(env, Error::ArithmeticError))
-            .checked_mul(10i128.pow(numer_decimals))
-            .unwrap_or_else(|| panic_with_error!(env, Error::ArithmeticError))
-            / (asset_lent
-                .checked_mul(10i128.pow(denom_decimals))
-                .unwrap_or_else(|| panic_with_error!(env, Error::ArithmeticError))
-                .checked_mul(xasset_price)
-                .unwrap_or_else(|| panic_with_error!(env, Error::ArithmeticError)))) as u32
-    };
-    collateralization_ratio
+) -> Result<u32, Error> {
+    if asset_lent == 0 || xasset_price == 0 {
+        return Ok(u32::MAX);
+    }
+    // Include accrued interest in the calculation: (a - i)b / (mp)
+    let effective_xlm = fixed_point::sub(xlm_deposited, accrued_interest)?;
+    let (numerator, denominator) = fixed_point::decimal_factors(xlm_decimals, xasset_decimals)?;
+    let ratio = fixed_point::div(
+        fixed_point::mul(
+            fixed_point::mul(fixed_point::mul(BASIS_POINTS, effective_xlm)?, xlm_price)?,
+            numerator,
+        )?,
+        fixed_point::mul(fixed_point::mul(asset_lent, xasset_price)?, denominator)?,
+        Rounding::Floor,
+    )?;
+    Ok(u32::try_from(ratio.max(0)).unwrap_or(u32::MAX))
 }
 
 // Persistent storage keys
@@ -124,6 +93,12 @@ const ADMIN_KEY: Symbol = symbol_short!("ADMIN");
 
 // Instance storage
 const STORAGE: Symbol = symbol_short!("STORAGE");
+// Settings added since the first release live under keys of their own rather than in
+// `TokenStorage`, and fall back to defaults when missing, so that contracts upgraded from an
+// earlier version keep decoding their state
+const ORACLES: Symbol = symbol_short!("ORACLES");
+const SUPPLY: Symbol = symbol_short!("SUPPLY");
+const COMPLIANCE: Symbol = symbol_short!("COMPLY");
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -226,6 +201,181 @@ impl TokenStorage {
     }
 }
 
+/// How the oracles are read. Defaults to our data-feed for both prices, with no staleness
+/// limit, fallback or TWAP check.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleSettings {
+    /// How prices are read from `xlm_contract`
+    xlm_adapter: OracleAdapter,
+    /// How prices are read from `asset_contract`
+    asset_adapter: OracleAdapter,
+    /// Decimals of `xlm_contract`'s prices, cached when it or its adapter is set. `None` if
+    /// the oracle couldn't be reached then, in which case they are fetched on every read.
+    xlm_decimals: Option<u32>,
+    /// Decimals of `asset_contract`'s prices, cached like `xlm_decimals`
+    asset_decimals: Option<u32>,
+    /// Maximum age of an oracle price in seconds; 0 if unlimited
+    max_price_age: u64,
+    /// What liquidations do when oracle prices are stale
+    stale_price_policy: StalePricePolicy,
+    /// Oracle read for XLM prices when `xlm_contract` is stale or fails
+    xlm_fallback: Option<Address>,
+    /// Oracle read for pegged asset prices when `asset_contract` is stale or fails
+    asset_fallback: Option<Address>,
+    /// Decimals of `xlm_fallback`'s prices, cached like `xlm_decimals`
+    xlm_fallback_decimals: Option<u32>,
+    /// Decimals of `asset_fallback`'s prices, cached like `xlm_decimals`
+    asset_fallback_decimals: Option<u32>,
+    /// Basis points the primary and fallback oracles may disagree by before risk-increasing
+    /// operations halt
+    max_oracle_deviation: u32,
+    /// Which prices a CDP must be below the MCR at to be frozen or liquidated
+    insolvency_mode: InsolvencyMode,
+}
+
+impl Default for OracleSettings {
+    fn default() -> Self {
+        OracleSettings {
+            xlm_adapter: OracleAdapter::DataFeed,
+            asset_adapter: OracleAdapter::DataFeed,
+            xlm_decimals: None,
+            asset_decimals: None,
+            max_price_age: 0,
+            stale_price_policy: StalePricePolicy::Halt,
+            xlm_fallback: None,
+            asset_fallback: None,
+            xlm_fallback_decimals: None,
+            asset_fallback_decimals: None,
+            max_oracle_deviation: MAX_ORACLE_DEVIATION,
+            insolvency_mode: InsolvencyMode::Spot,
+        }
+    }
+}
+
+impl OracleSettings {
+    fn get(env: &Env) -> OracleSettings {
+        env.storage().instance().get(&ORACLES).unwrap_or_default()
+    }
+
+    fn set(&self, env: &Env) {
+        env.storage().instance().set(&ORACLES, self);
+    }
+}
+
+/// Running totals of the xAsset's supply and of the debt backing it. Contracts upgraded from
+/// before these were tracked have none stored, and don't track them until they're backfilled.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Supply {
+    /// xAsset minted and not yet burned
+    total_supply: i128,
+    /// Principal owed by all CDPs
+    cdp_debt: i128,
+}
+
+impl Supply {
+    fn get(env: &Env) -> Supply {
+        env.storage().instance().get(&SUPPLY).unwrap_or_default()
+    }
+
+    /// The running totals, unless they're not tracked yet
+    fn get_tracked(env: &Env) -> Result<Supply, Error> {
+        env.storage()
+            .instance()
+            .get(&SUPPLY)
+            .ok_or(Error::SupplyNotTracked)
+    }
+
+    fn set(&self, env: &Env) {
+        env.storage().instance().set(&SUPPLY, self);
+    }
+
+    fn tracked(env: &Env) -> bool {
+        env.storage().instance().has(&SUPPLY)
+    }
+
+    fn add_total_supply(env: &Env, amount: i128) {
+        if !Supply::tracked(env) {
+            return;
+        }
+        let mut supply = Supply::get(env);
+        supply.total_supply = Self::checked_total(env, supply.total_supply, amount);
+        supply.set(env);
+    }
+
+    fn add_cdp_debt(env: &Env, amount: i128) {
+        if !Supply::tracked(env) {
+            return;
+        }
+        let mut supply = Supply::get(env);
+        supply.cdp_debt = Self::checked_total(env, supply.cdp_debt, amount);
+        supply.set(env);
+    }
+
+    /// `total + amount`, which must neither overflow nor be negative
+    fn checked_total(env: &Env, total: i128, amount: i128) -> i128 {
+        match total.checked_add(amount) {
+            Some(total) if total >= 0 => total,
+            _ => panic_with_error!(env, Error::ArithmeticError),
+        }
+    }
+}
+
+/// One of the two oracle feeds an xAsset reads
+#[derive(Clone, Copy)]
+enum Feed {
+    Xlm,
+    Asset,
+}
+
+/// Where and how to read a feed's prices
+struct Oracle {
+    contract: Address,
+    adapter: OracleAdapter,
+    asset: data_feed::Asset,
+    /// Decimals of this oracle and of the feed's primary oracle, if prices must be rescaled
+    rescale: Option<(u32, u32)>,
+}
+
+/// An oracle's most recent price and the bounds of its confidence interval
+struct Quote {
+    price: i128,
+    timestamp: u64,
+    lower: i128,
+    upper: i128,
+}
+
+/// What a feed's oracles reported, read once per invocation and shared by every check and
+/// price derived from it
+struct Reading {
+    /// Most recent price, from the primary oracle or else from the fallback
+    quote: Quote,
+    /// Most recent price that has cleared the oracle's security delay
+    delayed: i128,
+    /// Fallback oracle's spot price, if it was read to compare and both oracles are live
+    fallback: Option<i128>,
+}
+
+/// Oracle prices fetched once per invocation, with the decimals they're expressed in, and
+/// shared by every calculation in it
+struct Prices {
+    xlm: i128,
+    xlm_decimals: u32,
+    xasset: i128,
+    xasset_decimals: u32,
+}
+
+impl Oracle {
+    /// Express a price from this oracle in the primary oracle's decimals
+    fn scale(&self, price: i128) -> Result<i128, Error> {
+        match self.rescale {
+            Some((from, to)) => fixed_point::rescale(price, from, to, Rounding::Floor),
+            None => Ok(price),
+        }
+    }
+}
+
 #[contract]
 pub struct TokenContract;
 
@@ -267,7 +417,12 @@ impl TokenContract {
             interest_rate: annual_interest_rate,
             interest_collected: 0,
         };
+        let mut oracles = OracleSettings::default();
+        Self::refresh_decimals(env, &token, &mut oracles, Feed::Xlm);
+        Self::refresh_decimals(env, &token, &mut oracles, Feed::Asset);
         TokenStorage::set_state(env, &token);
+        oracles.set(env);
+        Supply::default().set(env);
     }
 
     /// Upgrade the contract to new wasm. Admin-only.
@@ -296,12 +451,37 @@ impl TokenContract {
     }
 
     // Fungible implementation. Implemented in a second impl block to reduce code diff in loam-migration
+    /// Set an allowance on behalf of `from`, emitting the SEP-41 `approve` event
     fn set_and_extend_allowance(
         env: &Env,
         from: Address,
         spender: Address,
         amount: i128,
         live_until_ledger: u32,
+    ) {
+        Self::write_allowance(
+            env,
+            from.clone(),
+            spender.clone(),
+            amount,
+            live_until_ledger,
+        );
+        Approve {
+            from,
+            spender,
+            amount,
+            expiration_ledger: live_until_ledger,
+        }
+        .publish(env);
+    }
+
+    /// Store an allowance and extend its TTL. Spending an allowance writes it without an event.
+    fn write_allowance(
+        env: &Env,
+        from: Address,
+        spender: Address,
+        amount: i128,
+        live_until_ledger: u32,
     ) {
         assert_positive(env, amount);
         let current_ledger = env.ledger().sequence();
@@ -365,7 +545,7 @@ impl TokenContract {
         let current_allowance = Self::allowance(env.clone(), from.clone(), spender.clone());
         let new_amount = current_allowance.checked_sub(amount).unwrap_or(0);
         let current_ledger = env.ledger().sequence();
-        Self::set_and_extend_allowance(
+        Self::write_allowance(
             env,
             from,
             spender,
@@ -402,19 +582,105 @@ impl TokenContract {
         Self::set_and_extend_authorized(env, id, authorize);
     }
 
+    /// Check whether only authorized addresses may send, receive, borrow or stake this xAsset
+    pub fn compliance_mode(env: &Env) -> bool {
+        env.storage().instance().get(&COMPLIANCE).unwrap_or(false)
+    }
+
+    /// Turn enforcement of `authorized` status on or off for this xAsset
+    pub fn set_compliance_mode(env: &Env, enabled: bool) {
+        Self::require_admin(env);
+        env.storage().instance().set(&COMPLIANCE, &enabled);
+        ComplianceModeSet { enabled }.publish(env);
+    }
+
+    /// Fail with `Unauthorized` if compliance mode is on and `id` is not authorized. The
+    /// contract itself, which holds the Stability Pool, is always allowed.
+    fn check_authorized(env: &Env, id: &Address) -> Result<(), Error> {
+        if !Self::compliance_mode(env)
+            || *id == env.current_contract_address()
+            || Self::authorized(env, id.clone())
+        {
+            Ok(())
+        } else {
+            Err(Error::Unauthorized)
+        }
+    }
+
+    /// Recompute the xAsset's supply from the balances of `holders` and the Stability Pool, and
+    /// its CDP debt from the CDPs of `lenders`, then track both from here on. Only needed, and
+    /// only allowed, once on contracts upgraded from before they were tracked. Admin-only.
+    ///
+    /// The contract can't enumerate balances or CDPs, so it trusts the lists to name every
+    /// holder and lender. Since xAsset is only minted against CDP debt, a supply above the debt
+    /// shows lenders are missing and is rejected; a missing holder can't be detected and leaves
+    /// the supply understated for good.
+    pub fn backfill_supply(
+        env: &Env,
+        holders: Vec<Address>,
+        lenders: Vec<Address>,
+    ) -> Result<(), Error> {
+        Self::require_admin(env);
+        if Supply::tracked(env) {
+            return Err(Error::SupplyAlreadyTracked);
+        }
+        // Each address counts once, however often it's listed
+        let mut balances = Map::new(env);
+        balances.set(
+            env.current_contract_address(),
+            Self::balance(env.clone(), env.current_contract_address()),
+        );
+        for holder in holders.iter() {
+            balances.set(holder.clone(), Self::balance(env.clone(), holder));
+        }
+        let mut debts = Map::new(env);
+        for lender in lenders.iter() {
+            let cdp = TokenStorage::get_cdp(env, lender.clone()).ok_or(Error::CDPNotFound)?;
+            debts.set(lender, cdp.asset_lent);
+        }
+
+        let mut supply = Supply::default();
+        for amount in balances.values() {
+            supply.total_supply = fixed_point::add(supply.total_supply, amount)?;
+        }
+        for amount in debts.values() {
+            supply.cdp_debt = fixed_point::add(supply.cdp_debt, amount)?;
+        }
+        if supply.total_supply < 0 || supply.cdp_debt < 0 {
+            return Err(Error::ArithmeticError);
+        }
+        if supply.total_supply > supply.cdp_debt {
+            return Err(Error::SupplyExceedsDebt);
+        }
+        supply.set(env);
+        Ok(())
+    }
+
+    /// Return the amount of xAsset minted and not yet burned. Fails until the supply is
+    /// backfilled on contracts upgraded from before it was tracked.
+    pub fn total_supply(env: &Env) -> Result<i128, Error> {
+        Ok(Supply::get_tracked(env)?.total_supply)
+    }
+
+    /// Break the xAsset's supply down into CDP debt, Stability Pool holdings and free float.
+    /// Fails until the supply is backfilled on contracts upgraded from before it was tracked.
+    pub fn protocol_accounting(env: &Env) -> Result<ProtocolAccounting, Error> {
+        let supply = Supply::get_tracked(env)?;
+        let stability_pool = TokenStorage::get_state(env).total_xasset;
+        Ok(ProtocolAccounting {
+            total_supply: supply.total_supply,
+            cdp_debt: supply.cdp_debt,
+            stability_pool,
+            free_float: supply.total_supply.saturating_sub(stability_pool),
+        })
+    }
+
     pub fn clawback(env: &Env, from: Address, amount: i128) {
         assert_positive(env, amount);
         Self::require_admin(env);
-        let Some(new_balance) = Self::balance(env.clone(), from.clone()).checked_sub(amount) else {
-            panic_with_error!(env, Error::ArithmeticError);
-        };
-        env.storage()
-            .persistent()
-            .set(&DataKey::Balance(from.clone()), &new_balance);
-        let ttl = env.storage().max_ttl();
-        env.storage()
-            .persistent()
-            .extend_ttl(&DataKey::Balance(from), ttl, ttl);
+        let balance = Self::balance(env.clone(), from.clone());
+        assert_with_error!(env, balance >= amount, Error::InsufficientBalance);
+        Self::burn_internal(env, from, amount, true);
     }
 
     // From second impl block
@@ -423,27 +689,23 @@ impl TokenContract {
         env: &Env,
         cdp: CDPInternal,
         lender: Address,
-        xlm_price: i128,
-        xlm_decimals: u32,
-        xasset_price: i128,
-        xasset_decimals: u32,
-    ) -> CDPContract {
+        prices: &Prices,
+    ) -> Result<CDPContract, Error> {
         // Update accrued interest first
         let (interest, last_interest_time) =
             Self::get_updated_accrued_interest(env, &cdp).unwrap_or_default();
 
         let collateralization_ratio = calculate_collateralization_ratio(
-            env,
             cdp.asset_lent,
-            xasset_price,
+            prices.xasset,
             cdp.xlm_deposited,
-            xlm_price,
-            xlm_decimals,
-            xasset_decimals,
+            prices.xlm,
+            prices.xlm_decimals,
+            prices.xasset_decimals,
             interest.amount,
-        );
+        )?;
 
-        CDPContract {
+        Ok(CDPContract {
             lender,
             xlm_deposited: cdp.xlm_deposited,
             asset_lent: cdp.asset_lent,
@@ -461,6 +723,376 @@ impl TokenContract {
             } else {
                 cdp.status
             },
+        })
+    }
+
+    /// Primary oracle for a feed
+    fn oracle(env: &Env, feed: Feed) -> Oracle {
+        let state = TokenStorage::get_state(env);
+        let oracles = OracleSettings::get(env);
+        let (contract, adapter, symbol) = match feed {
+            Feed::Xlm => (
+                state.xlm_contract,
+                oracles.xlm_adapter,
+                Symbol::new(env, "XLM"),
+            ),
+            Feed::Asset => (
+                state.asset_contract,
+                oracles.asset_adapter,
+                state.pegged_asset,
+            ),
+        };
+        let asset = match &adapter {
+            OracleAdapter::Sep40Stellar(address) => data_feed::Asset::Stellar(address.clone()),
+            _ => data_feed::Asset::Other(symbol),
+        };
+        Oracle {
+            contract,
+            adapter,
+            asset,
+            rescale: None,
+        }
+    }
+
+    /// Fallback oracle for a feed, if one is configured. It is read with the primary's adapter,
+    /// and its prices are rescaled to the primary's decimals; if either's decimals are
+    /// unavailable, so is the fallback.
+    fn fallback_oracle(env: &Env, feed: Feed) -> Option<Oracle> {
+        let oracles = OracleSettings::get(env);
+        let (contract, cached) = match feed {
+            Feed::Xlm => (oracles.xlm_fallback?, oracles.xlm_fallback_decimals),
+            Feed::Asset => (oracles.asset_fallback?, oracles.asset_fallback_decimals),
+        };
+        let primary = Self::oracle(env, feed);
+        let from = match cached {
+            Some(decimals) => decimals,
+            None => Self::fetch_decimals(env, &contract, &primary.adapter).ok()?,
+        };
+        let to = Self::oracle_decimals(env, feed).ok()?;
+        Some(Oracle {
+            contract,
+            rescale: (from != to).then_some((from, to)),
+            ..primary
+        })
+    }
+
+    /// Read a feed from its primary oracle, or from its fallback if the primary's price is stale
+    /// or can't be fetched. Fails with the primary's error if the fallback fails too.
+    fn with_fallback<T>(
+        env: &Env,
+        feed: Feed,
+        read: impl Fn(&Oracle) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        match read(&Self::oracle(env, feed)) {
+            Err(error @ (Error::OraclePriceStale | Error::OraclePriceFetchFailed)) => {
+                match Self::fallback_oracle(env, feed) {
+                    Some(fallback) => read(&fallback).map_err(|_| error),
+                    None => Err(error),
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Reject a price recorded more than `max_price_age` seconds ago
+    fn check_price_age(env: &Env, timestamp: u64) -> Result<(), Error> {
+        let max_age = OracleSettings::get(env).max_price_age;
+        if max_age > 0 && env.ledger().timestamp().saturating_sub(timestamp) > max_age {
+            return Err(Error::OraclePriceStale);
+        }
+        Ok(())
+    }
+
+    /// Fail if a feed's primary and fallback oracles are both live and their spot prices differ
+    /// by more than `max_oracle_deviation` basis points of the primary's price
+    fn check_oracle_agreement(env: &Env, reading: &Reading) -> Result<(), Error> {
+        let Some(fallback) = reading.fallback else {
+            return Ok(());
+        };
+        let primary = reading.quote.price;
+        let max_deviation = OracleSettings::get(env).max_oracle_deviation;
+        let tolerance = fixed_point::mul_div(
+            primary.saturating_abs(),
+            max_deviation as i128,
+            BASIS_POINTS,
+            Rounding::Floor,
+        )?;
+        if primary.saturating_sub(fallback).saturating_abs() > tolerance {
+            return Err(Error::OraclePriceDeviation);
+        }
+        Ok(())
+    }
+
+    /// Read a feed's oracles once. Prices come from the primary, or from the fallback if the
+    /// primary's price is stale or can't be fetched; if the fallback fails too, so does the
+    /// read, with the primary's error. A live primary's fallback is only read if `compare` is
+    /// set, for `check_oracle_agreement`. Unless `allow_stale`, fails if the feed's most
+    /// recent price is stale, i.e. the feed has stopped updating.
+    fn read_feed(
+        env: &Env,
+        feed: Feed,
+        allow_stale: bool,
+        compare: bool,
+    ) -> Result<Reading, Error> {
+        let primary = Self::oracle(env, feed);
+        let quote = Self::oracle_quote(env, &primary);
+        let status = match &quote {
+            Ok(quote) => Self::check_price_age(env, quote.timestamp),
+            Err(error) => Err(*error),
+        };
+        let read_fallback = || {
+            let oracle = Self::fallback_oracle(env, feed)?;
+            let quote = Self::oracle_quote(env, &oracle).ok()?;
+            Self::check_price_age(env, quote.timestamp).ok()?;
+            Some((oracle, quote))
+        };
+        let (oracle, quote, fallback) = match status {
+            Ok(()) => {
+                let fallback = if compare { read_fallback() } else { None };
+                (primary, quote?, fallback.map(|(_, quote)| quote.price))
+            }
+            Err(error @ (Error::OraclePriceStale | Error::OraclePriceFetchFailed)) => {
+                match read_fallback() {
+                    Some((oracle, quote)) => (oracle, quote, None),
+                    None if allow_stale && error == Error::OraclePriceStale => {
+                        (primary, quote?, None)
+                    }
+                    None => return Err(error),
+                }
+            }
+            Err(error) => return Err(error),
+        };
+        let delayed = Self::oracle_delayed_price(env, &oracle, &quote)?;
+        Ok(Reading {
+            quote,
+            delayed,
+            fallback,
+        })
+    }
+
+    /// Most recent price of a feed, rejected if it is stale
+    fn oracle_lastprice(env: &Env, feed: Feed) -> Result<PriceData, Error> {
+        Self::with_fallback(env, feed, |oracle| Self::oracle_fresh_price(env, oracle))
+    }
+
+    /// Most recent price from an oracle, rejected if it is stale
+    fn oracle_fresh_price(env: &Env, oracle: &Oracle) -> Result<PriceData, Error> {
+        let price = Self::oracle_read(env, oracle)?;
+        Self::check_price_age(env, price.timestamp)?;
+        Ok(price)
+    }
+
+    /// Most recent price from an oracle, whatever its age
+    fn oracle_read(env: &Env, oracle: &Oracle) -> Result<PriceData, Error> {
+        if let OracleAdapter::Fixed(price, _) = oracle.adapter {
+            return Ok(PriceData {
+                price,
+                timestamp: env.ledger().timestamp(),
+            });
+        }
+        let client = data_feed::Client::new(env, &oracle.contract);
+        match client.try_lastprice(&oracle.asset) {
+            Ok(price_data_option) => match price_data_option {
+                core::prelude::v1::Ok(Some(data_feed::PriceData { price, timestamp })) => {
+                    Ok(PriceData {
+                        price: oracle.scale(price)?,
+                        timestamp,
+                    })
+                }
+                core::prelude::v1::Ok(None) => Err(Error::OraclePriceFetchFailed),
+                Err(_) => Err(Error::OraclePriceFetchFailed),
+            },
+            Err(Ok(error)) if error == data_feed::Error::AssetDeprecated.into() => {
+                Err(Error::OracleAssetDeprecated)
+            }
+            Err(_) => Err(Error::OraclePriceFetchFailed),
+        }
+    }
+
+    /// Decimals of a feed's primary oracle, from the cache if they could be fetched when the
+    /// oracle was set
+    fn oracle_decimals(env: &Env, feed: Feed) -> Result<u32, Error> {
+        let oracles = OracleSettings::get(env);
+        let cached = match feed {
+            Feed::Xlm => oracles.xlm_decimals,
+            Feed::Asset => oracles.asset_decimals,
+        };
+        match cached {
+            Some(decimals) => Ok(decimals),
+            None => {
+                let oracle = Self::oracle(env, feed);
+                Self::fetch_decimals(env, &oracle.contract, &oracle.adapter)
+            }
+        }
+    }
+
+    /// Decimals an oracle reports its prices in, as read with the given adapter
+    fn fetch_decimals(
+        env: &Env,
+        contract: &Address,
+        adapter: &OracleAdapter,
+    ) -> Result<u32, Error> {
+        if let OracleAdapter::Fixed(_, decimals) = adapter {
+            return Ok(*decimals);
+        }
+        let client = data_feed::Client::new(env, contract);
+        match client.try_decimals() {
+            Ok(decimals_result) => match decimals_result {
+                core::prelude::v1::Ok(decimals) => Ok(decimals),
+                Err(_) => Err(Error::OracleDecimalsFetchFailed),
+            },
+            Err(_) => Err(Error::OracleDecimalsFetchFailed),
+        }
+    }
+
+    /// Most recent price from an oracle and the bounds of its confidence interval, whatever its
+    /// age. Only our data-feed reports a confidence; otherwise both bounds are the price.
+    fn oracle_quote(env: &Env, oracle: &Oracle) -> Result<Quote, Error> {
+        if oracle.adapter == OracleAdapter::DataFeed {
+            let client = data_feed::Client::new(env, &oracle.contract);
+            match client.try_lastprice_confidence(&oracle.asset) {
+                Ok(Ok(Some(record))) => {
+                    return Ok(Quote {
+                        price: oracle.scale(record.price)?,
+                        timestamp: record.timestamp,
+                        lower: oracle.scale(record.price.saturating_sub(record.confidence))?,
+                        upper: oracle.scale(record.price.saturating_add(record.confidence))?,
+                    });
+                }
+                Ok(Ok(None)) => return Err(Error::OraclePriceFetchFailed),
+                Err(Ok(error)) if error == data_feed::Error::AssetDeprecated.into() => {
+                    return Err(Error::OracleAssetDeprecated);
+                }
+                // Data-feeds deployed before confidences were reported
+                _ => {}
+            }
+        }
+        let PriceData { price, timestamp } = Self::oracle_read(env, oracle)?;
+        Ok(Quote {
+            price,
+            timestamp,
+            lower: price,
+            upper: price,
+        })
+    }
+
+    /// Time-weighted average of a feed's `records` most recent prices, as the oracle computes it.
+    /// Staleness is left to the spot price it's checked alongside.
+    fn oracle_twap(env: &Env, feed: Feed, records: u32) -> Result<i128, Error> {
+        Self::with_fallback(env, feed, |oracle| {
+            if let OracleAdapter::Fixed(price, _) = oracle.adapter {
+                return Ok(price);
+            }
+            let client = data_feed::Client::new(env, &oracle.contract);
+            let Ok(Ok(Some(price))) = client.try_twap(&oracle.asset, &records) else {
+                return Err(Error::OraclePriceFetchFailed);
+            };
+            oracle.scale(price)
+        })
+    }
+
+    /// Most recent price from an oracle that has cleared its security delay. Only our data-feed
+    /// delays prices; otherwise this is the quoted price.
+    fn oracle_delayed_price(env: &Env, oracle: &Oracle, quote: &Quote) -> Result<i128, Error> {
+        if oracle.adapter == OracleAdapter::DataFeed {
+            let client = data_feed::Client::new(env, &oracle.contract);
+            match client.try_current_price(&oracle.asset) {
+                Ok(Ok(Some(record))) => return oracle.scale(record.price),
+                Ok(Ok(None)) => return Err(Error::OraclePriceFetchFailed),
+                _ => {}
+            }
+        }
+        Ok(quote.price)
+    }
+
+    /// Price context for the given prices and the cached oracle decimals
+    fn prices(env: &Env, xlm: i128, xasset: i128) -> Result<Prices, Error> {
+        Ok(Prices {
+            xlm,
+            xlm_decimals: Self::oracle_decimals(env, Feed::Xlm)?,
+            xasset,
+            xasset_decimals: Self::oracle_decimals(env, Feed::Asset)?,
+        })
+    }
+
+    /// Spot prices, for settling interest
+    fn spot_prices(env: &Env) -> Result<Prices, Error> {
+        let xlm = Self::lastprice_xlm(env)?.price;
+        let xasset = Self::lastprice_asset(env)?.price;
+        Self::prices(env, xlm, xasset)
+    }
+
+    /// Delayed prices, for freezing and liquidating CDPs
+    fn delayed_prices(env: &Env, allow_stale: bool) -> Result<Prices, Error> {
+        let xlm = Self::read_feed(env, Feed::Xlm, allow_stale, false)?.delayed;
+        let xasset = Self::read_feed(env, Feed::Asset, allow_stale, false)?.delayed;
+        Self::prices(env, xlm, xasset)
+    }
+
+    /// Time-weighted average prices over the oracles' `records` most recent prices
+    fn twap_prices(env: &Env, records: u32) -> Result<Prices, Error> {
+        let xlm = Self::oracle_twap(env, Feed::Xlm, records)?;
+        let xasset = Self::oracle_twap(env, Feed::Asset, records)?;
+        Self::prices(env, xlm, xasset)
+    }
+
+    /// Under `InsolvencyMode::SpotAndTwap`, fail unless a CDP is below the MCR at time-weighted
+    /// average prices as well as at the prices it was already judged by
+    fn check_twap_insolvency(env: &Env, cdp: CDPInternal, lender: Address) -> Result<(), Error> {
+        let InsolvencyMode::SpotAndTwap(records) = OracleSettings::get(env).insolvency_mode else {
+            return Ok(());
+        };
+        let prices = Self::twap_prices(env, records)?;
+        if Self::decorate(env, cdp, lender, &prices)?.collateralization_ratio
+            >= Self::minimum_collateralization_ratio(env)
+        {
+            return Err(Error::CDPNotInsolvent);
+        }
+        Ok(())
+    }
+
+    /// Strict prices, for taking on risk: the lower XLM price and the higher pegged asset price
+    /// of the delayed price and the newest price's confidence interval. Fails while either
+    /// feed's oracles disagree.
+    fn strict_prices(env: &Env) -> Result<Prices, Error> {
+        let xlm = Self::read_feed(env, Feed::Xlm, false, true)?;
+        let xasset = Self::read_feed(env, Feed::Asset, false, true)?;
+        Self::check_oracle_agreement(env, &xlm)?;
+        Self::check_oracle_agreement(env, &xasset)?;
+        Self::prices(
+            env,
+            xlm.quote.lower.min(xlm.delayed),
+            xasset.quote.upper.max(xasset.delayed),
+        )
+    }
+
+    /// Fetch the decimals of a feed's primary and fallback oracles and cache them. Oracles that
+    /// can't be reached, e.g. because they're deployed after this contract, aren't cached.
+    fn refresh_decimals(env: &Env, state: &TokenStorage, oracles: &mut OracleSettings, feed: Feed) {
+        let (contract, adapter, fallback) = match feed {
+            Feed::Xlm => (
+                &state.xlm_contract,
+                &oracles.xlm_adapter,
+                &oracles.xlm_fallback,
+            ),
+            Feed::Asset => (
+                &state.asset_contract,
+                &oracles.asset_adapter,
+                &oracles.asset_fallback,
+            ),
+        };
+        let fetch = |contract: &Address| Self::fetch_decimals(env, contract, adapter).ok();
+        let decimals = fetch(contract);
+        let fallback_decimals = fallback.as_ref().and_then(fetch);
+        match feed {
+            Feed::Xlm => {
+                oracles.xlm_decimals = decimals;
+                oracles.xlm_fallback_decimals = fallback_decimals;
+            }
+            Feed::Asset => {
+                oracles.asset_decimals = decimals;
+                oracles.asset_fallback_decimals = fallback_decimals;
+            }
         }
     }
 
@@ -505,6 +1137,9 @@ impl TokenContract {
 
     // Mint asset, internal only as all assets should be backed by collateral
     fn mint_internal(env: &Env, to: Address, amount: i128) {
+        if let Err(err) = Self::check_authorized(env, &to) {
+            panic_with_error!(env, err);
+        }
         let balance: i128 = env
             .storage()
             .persistent()
@@ -516,10 +1151,23 @@ impl TokenContract {
         env.storage()
             .persistent()
             .set(&DataKey::Balance(to.clone()), &new_balance);
-        MintXasset { to, amount }.publish(env);
+        Supply::add_total_supply(env, amount);
+        MintXasset {
+            to: to.clone(),
+            amount,
+        }
+        .publish(env);
+        Mint { to, amount }.publish(env);
     }
 
-    fn transfer_internal(env: &Env, from: Address, to: Address, amount: i128) {
+    fn transfer_internal(env: &Env, from: Address, to: impl Into<MuxedAddress>, amount: i128) {
+        let to_muxed: MuxedAddress = to.into();
+        let to = to_muxed.address();
+        if let Err(err) =
+            Self::check_authorized(env, &from).and_then(|_| Self::check_authorized(env, &to))
+        {
+            panic_with_error!(env, err);
+        }
         let curr_from_balance: i128 = env
             .storage()
             .persistent()
@@ -542,9 +1190,20 @@ impl TokenContract {
         env.storage()
             .persistent()
             .set(&DataKey::Balance(to.clone()), &to_balance);
+        match to_muxed.id() {
+            Some(to_muxed_id) => TransferMuxed {
+                from,
+                to,
+                to_muxed_id,
+                amount,
+            }
+            .publish(env),
+            None => Transfer { from, to, amount }.publish(env),
+        }
     }
 
-    fn burn_internal(env: &Env, from: Address, amount: i128) {
+    // Burn asset, reporting it as a clawback by the admin rather than a burn if `clawback` is true
+    fn burn_internal(env: &Env, from: Address, amount: i128, clawback: bool) {
         let balance: i128 = env
             .storage()
             .persistent()
@@ -556,7 +1215,21 @@ impl TokenContract {
         env.storage()
             .persistent()
             .set(&DataKey::Balance(from.clone()), &new_balance);
-        BurnXasset { from, amount }.publish(env);
+        let ttl = env.storage().max_ttl();
+        env.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Balance(from.clone()), ttl, ttl);
+        Supply::add_total_supply(env, -amount);
+        BurnXasset {
+            from: from.clone(),
+            amount,
+        }
+        .publish(env);
+        if clawback {
+            Clawback { from, amount }.publish(env);
+        } else {
+            Burn { from, amount }.publish(env);
+        }
     }
 
     // withdraw the amount specified unless full_withdrawal is true in which case withdraw remaining balance
@@ -568,11 +1241,11 @@ impl TokenContract {
     ) -> Result<(), Error> {
         let position = Self::get_deposit(env, to.clone())
             .unwrap_or_else(|| panic_with_error!(env, Error::StakeDoesntExist));
-        let rewards = Self::calculate_rewards(env, &position);
+        let rewards = Self::calculate_rewards(env, &position)?;
         if rewards > 0 {
             return Err(Error::ClaimRewardsFirst);
         }
-        let xasset_owed = Self::calculate_current_deposit(env, &position);
+        let xasset_owed = Self::calculate_current_deposit(env, &position)?;
         let amount_to_withdraw = if full_withdrawal { xasset_owed } else { amount };
         if xasset_owed < amount_to_withdraw {
             return Err(Error::InsufficientStake);
@@ -634,55 +1307,61 @@ impl TokenContract {
         Ok(())
     }
 
-    fn calculate_current_deposit(env: &Env, position: &StakerPosition) -> i128 {
+    fn calculate_current_deposit(env: &Env, position: &StakerPosition) -> Result<i128, Error> {
         if position.epoch == Self::get_epoch(env) {
-            let value =
-                (DEFAULT_PRECISION * position.xasset_deposit * Self::get_product_constant(env))
-                    / position.product_constant;
-            bankers_round(value, DEFAULT_PRECISION)
+            fixed_point::mul_div(
+                position.xasset_deposit,
+                Self::get_product_constant(env),
+                position.product_constant,
+                Rounding::Bankers,
+            )
         } else {
-            0
+            Ok(0)
         }
     }
 
-    fn calculate_rewards(env: &Env, position: &StakerPosition) -> i128 {
-        if position.epoch == Self::get_epoch(env) {
-            let value = (DEFAULT_PRECISION
-                * position.xasset_deposit
-                * (Self::get_compounded_constant(env) - position.compounded_constant))
-                / position.product_constant;
-            bankers_round(value, DEFAULT_PRECISION)
+    fn calculate_rewards(env: &Env, position: &StakerPosition) -> Result<i128, Error> {
+        let compounded_constant = if position.epoch == Self::get_epoch(env) {
+            Self::get_compounded_constant(env)
         } else {
-            let value = (DEFAULT_PRECISION
-                * position.xasset_deposit
-                * (Self::get_compounded_epoch(env, position.epoch)
-                    .expect("The historical compounded constant should always be recorded")
-                    - position.compounded_constant))
-                / position.product_constant;
-            bankers_round(value, DEFAULT_PRECISION)
-        }
+            Self::get_compounded_epoch(env, position.epoch)
+                .expect("The historical compounded constant should always be recorded")
+        };
+        fixed_point::mul_div(
+            position.xasset_deposit,
+            fixed_point::sub(compounded_constant, position.compounded_constant)?,
+            position.product_constant,
+            Rounding::Bankers,
+        )
     }
 
-    fn update_constants(env: &Env, xasset_debited: i128, xlm_earned: i128) {
+    fn update_constants(env: &Env, xasset_debited: i128, xlm_earned: i128) -> Result<(), Error> {
         // Check if total_xasset is zero prior to calculation
         let total_xasset = Self::get_total_xasset(env);
         let product_constant = Self::get_product_constant(env);
         if total_xasset == 0 {
             Self::increment_epoch(env);
-            return;
+            return Ok(());
         }
 
         // Proceed with updates if total_xasset is not zero
-        let new_product_constant =
-            (product_constant * (total_xasset - xasset_debited)) / total_xasset;
-        let new_compounded_constant =
-            Self::get_compounded_constant(env) + (xlm_earned * product_constant) / total_xasset;
+        let new_product_constant = fixed_point::mul_div(
+            product_constant,
+            fixed_point::sub(total_xasset, xasset_debited)?,
+            total_xasset,
+            Rounding::Floor,
+        )?;
+        let new_compounded_constant = fixed_point::add(
+            Self::get_compounded_constant(env),
+            fixed_point::mul_div(xlm_earned, product_constant, total_xasset, Rounding::Floor)?,
+        )?;
 
         Self::set_product_constant(env, new_product_constant);
         Self::set_compounded_constant(env, new_compounded_constant);
         if total_xasset == xasset_debited {
             Self::increment_epoch(env);
         }
+        Ok(())
     }
 
     fn increment_epoch(env: &Env) {
@@ -885,16 +1564,20 @@ impl TokenContract {
         Ok((interest, now))
     }
 
+    /// Pay accrued interest on a CDP at the given spot prices, through `pay_fn`
     fn apply_interest_payment<F>(
         env: &Env,
         lender: Address,
         amount_in_xasset: Option<i128>,
+        prices: &Prices,
         pay_fn: F,
     ) -> Result<CDPContract, Error>
     where
         F: FnOnce(&Address, &i128) -> Result<(), Error>,
     {
-        let cdp = Self::cdp(env, lender.clone()).unwrap();
+        let internal = TokenStorage::get_cdp(env, lender.clone())
+            .unwrap_or_else(|| panic_with_error!(env, Error::CDPNotFound));
+        let cdp = Self::decorate(env, internal, lender.clone(), prices)?;
         let mut interest = cdp.accrued_interest;
         // if called with None, it means we want to pay off all currently accrued interest
         let amount_to_pay = match amount_in_xasset {
@@ -909,11 +1592,7 @@ impl TokenContract {
         if amount_to_pay == 0 {
             return Ok(cdp);
         }
-        let price = Self::lastprice_asset(env).unwrap();
-        let xlmprice = Self::lastprice_xlm(env).unwrap();
-        let xasset_decimals = Self::decimals_asset_feed(env)?;
-        let xlm_decimals = Self::decimals_xlm_feed(env)?;
-        let amount_in_xlm = Self::convert_xasset_to_xlm(env, amount_to_pay)?;
+        let amount_in_xlm = Self::convert_xasset_to_xlm(amount_to_pay, prices, Rounding::Bankers)?;
         if Self::native(env).balance(&lender) < amount_in_xlm {
             return Err(Error::InsufficientXLMForInterest);
         }
@@ -940,41 +1619,44 @@ impl TokenContract {
                 last_interest_time: cdp.last_interest_time,
             },
             lender.clone(),
-            xlmprice.price,
-            xlm_decimals,
-            price.price,
-            xasset_decimals,
-        );
+            prices,
+        )?;
 
         Self::set_cdp_from_decorated(env, lender, decorated_cdp.clone());
         TokenStorage::set_interest_collected(
             env,
-            Self::get_total_interest_collected(env) + amount_in_xlm,
+            fixed_point::add(Self::get_total_interest_collected(env), amount_in_xlm)?,
         );
-        Self::increment_interest_for_current_epoch(env, &amount_in_xlm);
+        Self::increment_interest_for_current_epoch(env, &amount_in_xlm)?;
 
         Ok(decorated_cdp)
     }
 
-    fn convert_xasset_to_xlm(env: &Env, amount_in_xasset: i128) -> Result<i128, Error> {
-        let price = Self::lastprice_asset(env).unwrap();
-        let xlmprice = Self::lastprice_xlm(env).unwrap();
-        let xasset_decimals = Self::decimals_asset_feed(env)?;
-        let xlm_decimals = Self::decimals_xlm_feed(env)?;
-        Ok(bankers_round(
-            (DEFAULT_PRECISION
-                * amount_in_xasset
-                * price.price
-                * 10i128.pow(xlm_decimals - xasset_decimals))
-                / (xlmprice.price),
-            DEFAULT_PRECISION,
-        ))
+    /// Convert an xAsset amount to XLM at the given oracle prices
+    fn convert_xasset_to_xlm(
+        amount_in_xasset: i128,
+        prices: &Prices,
+        rounding: Rounding,
+    ) -> Result<i128, Error> {
+        fixed_point::convert(
+            amount_in_xasset,
+            prices.xasset,
+            prices.xasset_decimals,
+            prices.xlm,
+            prices.xlm_decimals,
+            rounding,
+        )
     }
 
-    fn increment_interest_for_current_epoch(env: &Env, amount: &i128) {
+    fn increment_interest_for_current_epoch(env: &Env, amount: &i128) -> Result<(), Error> {
         let current_epoch = Self::get_epoch(env);
         let current_interest = Self::get_interest_record(env, current_epoch);
-        Self::set_and_extend_interest_record(env, current_epoch, &(current_interest + amount));
+        Self::set_and_extend_interest_record(
+            env,
+            current_epoch,
+            &fixed_point::add(current_interest, *amount)?,
+        );
+        Ok(())
     }
 
     // Helper to calculate projected interest at a future timestamp
@@ -994,13 +1676,14 @@ impl TokenContract {
             return Ok(cdp.accrued_interest);
         }
 
-        let interest_amount = bankers_round(
-            cdp.asset_lent * annual_rate * (time_elapsed as i128) * INTEREST_PRECISION
-                / (BASIS_POINTS * (SECONDS_PER_YEAR as i128)),
-            INTEREST_PRECISION,
-        );
+        let interest_amount = fixed_point::mul_div(
+            cdp.asset_lent,
+            fixed_point::mul(annual_rate, time_elapsed as i128)?,
+            BASIS_POINTS * (SECONDS_PER_YEAR as i128),
+            Rounding::Bankers,
+        )?;
         Ok(Interest {
-            amount: cdp.accrued_interest.amount + interest_amount,
+            amount: fixed_point::add(cdp.accrued_interest.amount, interest_amount)?,
             paid: cdp.accrued_interest.paid,
         })
     }
@@ -1026,24 +1709,7 @@ impl TokenInterface for TokenContract {
     /// Set the allowance by `amount` for `spender` to transfer/burn from `from`
     fn approve(env: Env, from: Address, spender: Address, amount: i128, live_until_ledger: u32) {
         from.require_auth();
-        let current_ledger = env.ledger().sequence();
-        assert_positive(&env, amount);
-        assert_with_error!(
-            env,
-            live_until_ledger >= current_ledger,
-            Error::InvalidLedgerSequence
-        );
-        let max_ttl = env.storage().max_ttl();
-        env.storage().persistent().set(
-            &Txn(from.clone(), spender.clone()),
-            &Allowance {
-                amount,
-                live_until_ledger,
-            },
-        );
-        env.storage()
-            .persistent()
-            .extend_ttl(&Txn(from, spender), max_ttl, max_ttl);
+        Self::set_and_extend_allowance(&env, from, spender, amount, live_until_ledger);
     }
 
     /// Return the balance of `id`
@@ -1065,7 +1731,7 @@ impl TokenInterface for TokenContract {
         );
         let balance = Self::balance(env.clone(), from.clone());
         assert_with_error!(env, balance >= amount, Error::InsufficientBalance);
-        Self::transfer_internal(&env, from, to.address(), amount);
+        Self::transfer_internal(&env, from, to, amount);
     }
 
     /// Transfer `amount` from `from` to `to`, consuming the allowance of `spender`
@@ -1093,7 +1759,7 @@ impl TokenInterface for TokenContract {
         assert_with_error!(env.clone(), amount > 0, Error::ValueNotPositive);
         let balance = Self::balance(env.clone(), from.clone());
         assert_with_error!(env.clone(), balance >= amount, Error::InsufficientBalance);
-        Self::burn_internal(&env, from, amount);
+        Self::burn_internal(&env, from, amount, false);
     }
 
     /// Burn `amount` from `from`, consuming the allowance of `spender`
@@ -1146,6 +1812,46 @@ impl IsCollateralized for TokenContract {
         TokenStorage::get_state(env).pegged_asset.clone()
     }
 
+    /// How prices are read from the XLM oracle contract
+    fn xlm_adapter(env: &Env) -> OracleAdapter {
+        OracleSettings::get(env).xlm_adapter
+    }
+
+    /// How prices are read from the pegged asset oracle contract
+    fn asset_adapter(env: &Env) -> OracleAdapter {
+        OracleSettings::get(env).asset_adapter
+    }
+
+    /// Fallback oracle for XLM prices, if any
+    fn xlm_fallback(env: &Env) -> Option<Address> {
+        OracleSettings::get(env).xlm_fallback
+    }
+
+    /// Fallback oracle for pegged asset prices, if any
+    fn asset_fallback(env: &Env) -> Option<Address> {
+        OracleSettings::get(env).asset_fallback
+    }
+
+    /// Basis points the primary and fallback oracles may disagree by. Default: 5%
+    fn max_oracle_deviation(env: &Env) -> u32 {
+        OracleSettings::get(env).max_oracle_deviation
+    }
+
+    /// Which prices a CDP must be below the MCR at to be frozen or liquidated
+    fn insolvency_mode(env: &Env) -> InsolvencyMode {
+        OracleSettings::get(env).insolvency_mode
+    }
+
+    /// Maximum age of an oracle price in seconds before reads are rejected; 0 if unlimited
+    fn max_price_age(env: &Env) -> u64 {
+        OracleSettings::get(env).max_price_age
+    }
+
+    /// What liquidations do when oracle prices are stale
+    fn stale_price_policy(env: &Env) -> StalePricePolicy {
+        OracleSettings::get(env).stale_price_policy
+    }
+
     /// Basis points. Default: 110%
     fn minimum_collateralization_ratio(env: &Env) -> u32 {
         TokenStorage::get_state(env).min_collat_ratio
@@ -1153,64 +1859,22 @@ impl IsCollateralized for TokenContract {
 
     /// Get the most recent price for XLM
     fn lastprice_xlm(env: &Env) -> Result<PriceData, Error> {
-        let contract = &Self::xlm_contract(env);
-        let client = data_feed::Client::new(env, contract);
-        match client.try_lastprice(&data_feed::Asset::Other(Symbol::new(env, "XLM"))) {
-            Ok(price_data_option) => match price_data_option {
-                core::prelude::v1::Ok(Some(data_feed::PriceData { price, timestamp })) => {
-                    Ok(PriceData { price, timestamp })
-                }
-                core::prelude::v1::Ok(None) => Err(Error::OraclePriceFetchFailed),
-                Err(_) => Err(Error::OraclePriceFetchFailed),
-            },
-            Err(_) => Err(Error::OraclePriceFetchFailed),
-        }
+        Self::oracle_lastprice(env, Feed::Xlm)
     }
 
     /// Get the most recent price for the pegged asset
     fn lastprice_asset(env: &Env) -> Result<PriceData, Error> {
-        let contract = Self::asset_contract(env);
-        let asset = Self::pegged_asset(env);
-        let client = data_feed::Client::new(env, &contract);
-
-        match client.try_lastprice(&data_feed::Asset::Other(asset.clone())) {
-            Ok(price_data_option) => match price_data_option {
-                core::prelude::v1::Ok(Some(data_feed::PriceData { price, timestamp })) => {
-                    Ok(PriceData { price, timestamp })
-                }
-                core::prelude::v1::Ok(None) => Err(Error::OraclePriceFetchFailed),
-                Err(_) => Err(Error::OraclePriceFetchFailed),
-            },
-            Err(_) => Err(Error::OraclePriceFetchFailed),
-        }
+        Self::oracle_lastprice(env, Feed::Asset)
     }
 
     /// Get the number of decimals used by the xlm oracle contract. This is NOT the same as the number of decimals used by the XLM Stellar Asset Contract.
     fn decimals_xlm_feed(env: &Env) -> Result<u32, Error> {
-        let contract = &Self::xlm_contract(env);
-        let client = data_feed::Client::new(env, contract);
-
-        match client.try_decimals() {
-            Ok(decimals_result) => match decimals_result {
-                core::prelude::v1::Ok(decimals) => Ok(decimals),
-                Err(_) => Err(Error::OracleDecimalsFetchFailed),
-            },
-            Err(_) => Err(Error::OracleDecimalsFetchFailed),
-        }
+        Self::oracle_decimals(env, Feed::Xlm)
     }
 
     /// Get the number of decimals used by the asset oracle contract. This is NOT the same as the number of decimals used by the xAsset Fungible Token contract.
     fn decimals_asset_feed(env: &Env) -> Result<u32, Error> {
-        let contract = &Self::asset_contract(env);
-        let client = data_feed::Client::new(env, contract);
-
-        match client.try_decimals() {
-            Ok(decimals_result) => match decimals_result {
-                core::prelude::v1::Ok(decimals) => Ok(decimals),
-                Err(_) => Err(Error::OracleDecimalsFetchFailed),
-            },
-            Err(_) => Err(Error::OracleDecimalsFetchFailed),
-        }
+        Self::oracle_decimals(env, Feed::Asset)
     }
 
     /// Open a new Collateralized Debt Position (CDP) by depositing collateral and minting xAsset
@@ -1223,6 +1887,7 @@ impl IsCollateralized for TokenContract {
         assert_positive(env, collateral);
         assert_positive(env, asset_lent);
         lender.require_auth();
+        Self::check_authorized(env, &lender)?;
 
         let cdp: Option<CDPInternal> = env
             .storage()
@@ -1235,22 +1900,11 @@ impl IsCollateralized for TokenContract {
 
         // 2. check that `lastprice` gives collateralization ratio over `min_collat_ratio`
         let cdp = CDPInternal::new(collateral, asset_lent, env.ledger().timestamp());
-        let xlm_price = Self::lastprice_xlm(env)?;
-        let xlm_decimals = Self::decimals_xlm_feed(env)?;
-        let xasset_price = Self::lastprice_asset(env)?;
-        let xasset_decimals = Self::decimals_asset_feed(env)?;
+        let prices = Self::strict_prices(env)?;
         let CDPContract {
             collateralization_ratio,
             ..
-        } = Self::decorate(
-            env,
-            cdp,
-            lender.clone(),
-            xlm_price.price,
-            xlm_decimals,
-            xasset_price.price,
-            xasset_decimals,
-        );
+        } = Self::decorate(env, cdp, lender.clone(), &prices)?;
         if collateralization_ratio < Self::minimum_collateralization_ratio(env) {
             return Err(Error::InsufficientCollateralization);
         }
@@ -1262,6 +1916,7 @@ impl IsCollateralized for TokenContract {
 
         // 4. mint `asset_lent` of this token to `address`
         Self::mint_internal(env, lender.clone(), asset_lent);
+        Supply::add_cdp_debt(env, asset_lent);
 
         // 5. create CDP
         env.storage()
@@ -1289,26 +1944,18 @@ impl IsCollateralized for TokenContract {
     fn cdp(env: &Env, lender: Address) -> Result<CDPContract, Error> {
         let cdp = TokenStorage::get_cdp(env, lender.clone())
             .unwrap_or_else(|| panic_with_error!(env, Error::CDPNotFound));
-        let xlm_price = Self::lastprice_xlm(env)?;
-        let xlm_decimals = Self::decimals_xlm_feed(env)?;
-        let xasset_price = Self::lastprice_asset(env)?;
-        let xasset_decimals = Self::decimals_asset_feed(env)?;
-        Ok(Self::decorate(
-            env,
-            cdp,
-            lender,
-            xlm_price.price,
-            xlm_decimals,
-            xasset_price.price,
-            xasset_decimals,
-        ))
+        let prices = Self::delayed_prices(env, false)?;
+        Self::decorate(env, cdp, lender, &prices)
     }
 
     /// Freeze a CDP if its Collateralization Ratio (CR) is below the xAsset's Minimum Collateralization Ratio (MCR).
     /// A frozen CDP is no longer usable or interactable by its former owner.
     fn freeze_cdp(env: &Env, lender: Address) -> Result<(), Error> {
+        let internal = TokenStorage::get_cdp(env, lender.clone())
+            .unwrap_or_else(|| panic_with_error!(env, Error::CDPNotFound));
         let mut cdp = Self::cdp(env, lender.clone())?;
         if matches!(cdp.status, CDPStatus::Insolvent) {
+            Self::check_twap_insolvency(env, internal, lender.clone())?;
             cdp.status = CDPStatus::Frozen;
             Self::set_cdp_from_decorated(env, lender, cdp);
             Ok(())
@@ -1369,11 +2016,8 @@ impl IsCollateralized for TokenContract {
                 last_interest_time: cdp.last_interest_time,
             },
             lender.clone(),
-            Self::lastprice_xlm(env)?.price,
-            Self::decimals_xlm_feed(env)?,
-            Self::lastprice_asset(env)?.price,
-            Self::decimals_asset_feed(env)?,
-        );
+            &Self::strict_prices(env)?,
+        )?;
 
         if new_cdp.collateralization_ratio < Self::minimum_collateralization_ratio(env) {
             return Err(Error::InvalidWithdrawal);
@@ -1419,11 +2063,8 @@ impl IsCollateralized for TokenContract {
                 last_interest_time: cdp.last_interest_time,
             },
             lender.clone(),
-            Self::lastprice_xlm(env)?.price,
-            Self::decimals_xlm_feed(env)?,
-            Self::lastprice_asset(env)?.price,
-            Self::decimals_asset_feed(env)?,
-        );
+            &Self::strict_prices(env)?,
+        )?;
 
         if new_cdp.collateralization_ratio < Self::minimum_collateralization_ratio(env) {
             return Err(Error::InsufficientCollateralization);
@@ -1431,6 +2072,7 @@ impl IsCollateralized for TokenContract {
 
         // mint xasset
         Self::mint_internal(env, lender.clone(), amount);
+        Supply::add_cdp_debt(env, amount);
 
         Self::set_cdp_from_decorated(env, lender, new_cdp);
         Ok(())
@@ -1448,7 +2090,8 @@ impl IsCollateralized for TokenContract {
         }
 
         // Pay off any interest first
-        Self::apply_interest_payment(env, lender.clone(), None, |from, amount_in_xlm| {
+        let prices = Self::spot_prices(env)?;
+        Self::apply_interest_payment(env, lender.clone(), None, &prices, |from, amount_in_xlm| {
             match Self::native(env).try_transfer_from(
                 &env.current_contract_address(),
                 from,
@@ -1480,7 +2123,8 @@ impl IsCollateralized for TokenContract {
         };
 
         // Burn the xasset
-        Self::burn_internal(env, lender.clone(), amount);
+        Self::burn_internal(env, lender.clone(), amount, false);
+        Supply::add_cdp_debt(env, -amount);
 
         cdp.asset_lent = asset_lent;
 
@@ -1600,10 +2244,14 @@ impl IsCollateralized for TokenContract {
         // Project interest 5 minutes ahead
         let projected_interest =
             Self::get_projected_interest(env, &cdp, cdp.last_interest_time, five_min_later)?;
-        let approval_amount = Self::convert_xasset_to_xlm(env, projected_interest.amount)?;
+        let prices = Self::spot_prices(env)?;
+        // Round up so the approval always covers the payment
+        let approval_amount =
+            Self::convert_xasset_to_xlm(projected_interest.amount, &prices, Rounding::Ceil)?;
 
         // Calculate interest in XLM
-        let amount_in_xlm = Self::convert_xasset_to_xlm(env, interest.amount)?;
+        let amount_in_xlm =
+            Self::convert_xasset_to_xlm(interest.amount, &prices, Rounding::Bankers)?;
 
         Ok(InterestDetail {
             amount: interest.amount,
@@ -1626,10 +2274,12 @@ impl IsCollateralized for TokenContract {
         if amount_in_xasset <= 0 {
             return Err(Error::ValueNotPositive);
         }
+        let prices = Self::spot_prices(env)?;
         Self::apply_interest_payment(
             env,
             lender,
             Some(amount_in_xasset),
+            &prices,
             |lender, amount_in_xlm| {
                 match Self::native(env).try_transfer(
                     lender,
@@ -1660,7 +2310,10 @@ impl IsCDPAdmin for TokenContract {
         Self::require_admin(env);
         let mut state = TokenStorage::get_state(env);
         state.xlm_contract = to;
+        let mut oracles = OracleSettings::get(env);
+        Self::refresh_decimals(env, &state, &mut oracles, Feed::Xlm);
         TokenStorage::set_state(env, &state);
+        oracles.set(env);
     }
 
     /// Set the oracle price feed contract for xAsset
@@ -1668,7 +2321,10 @@ impl IsCDPAdmin for TokenContract {
         Self::require_admin(env);
         let mut state = TokenStorage::get_state(env);
         state.asset_contract = to;
+        let mut oracles = OracleSettings::get(env);
+        Self::refresh_decimals(env, &state, &mut oracles, Feed::Asset);
         TokenStorage::set_state(env, &state);
+        oracles.set(env);
     }
 
     /// Set the asset the xAsset is pegged to
@@ -1679,6 +2335,84 @@ impl IsCDPAdmin for TokenContract {
         TokenStorage::set_state(env, &state);
     }
 
+    /// Set how prices are read from the XLM oracle contract
+    fn set_xlm_adapter(env: &Env, to: OracleAdapter) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.xlm_adapter = to;
+        Self::refresh_decimals(env, &TokenStorage::get_state(env), &mut oracles, Feed::Xlm);
+        oracles.set(env);
+    }
+
+    /// Set how prices are read from the pegged asset oracle contract
+    fn set_asset_adapter(env: &Env, to: OracleAdapter) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.asset_adapter = to;
+        Self::refresh_decimals(
+            env,
+            &TokenStorage::get_state(env),
+            &mut oracles,
+            Feed::Asset,
+        );
+        oracles.set(env);
+    }
+
+    /// Set the fallback oracle for XLM prices
+    fn set_xlm_fallback(env: &Env, to: Option<Address>) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.xlm_fallback = to;
+        Self::refresh_decimals(env, &TokenStorage::get_state(env), &mut oracles, Feed::Xlm);
+        oracles.set(env);
+    }
+
+    /// Set the fallback oracle for pegged asset prices
+    fn set_asset_fallback(env: &Env, to: Option<Address>) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.asset_fallback = to;
+        Self::refresh_decimals(
+            env,
+            &TokenStorage::get_state(env),
+            &mut oracles,
+            Feed::Asset,
+        );
+        oracles.set(env);
+    }
+
+    /// Set the basis points the primary and fallback oracles may disagree by
+    fn set_max_oracle_deviation(env: &Env, to: u32) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.max_oracle_deviation = to;
+        oracles.set(env);
+    }
+
+    /// Set which prices a CDP must be below the MCR at to be frozen or liquidated
+    fn set_insolvency_mode(env: &Env, to: InsolvencyMode) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.insolvency_mode = to;
+        oracles.set(env);
+    }
+
+    /// Set the maximum age of an oracle price in seconds
+    fn set_max_price_age(env: &Env, to: u64) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.max_price_age = to;
+        oracles.set(env);
+    }
+
+    /// Set what liquidations do when oracle prices are stale
+    fn set_stale_price_policy(env: &Env, to: StalePricePolicy) {
+        Self::require_admin(env);
+        let mut oracles = OracleSettings::get(env);
+        oracles.stale_price_policy = to;
+        oracles.set(env);
+    }
+
     /// Set minimum collateralization ratio
     fn set_min_collat_ratio(env: &Env, to: u32) -> u32 {
         Self::require_admin(env);
@@ -1717,6 +2451,7 @@ impl IsStabilityPool for TokenContract {
     fn deposit(env: &Env, from: Address, amount: i128) -> Result<(), Error> {
         assert_positive(env, amount);
         from.require_auth();
+        Self::check_authorized(env, &from)?;
         // check if the user has sufficient xasset
         let balance = Self::balance(env.clone(), from.clone());
         if balance < amount {
@@ -1729,7 +2464,7 @@ impl IsStabilityPool for TokenContract {
             compounded_constant: Self::get_compounded_constant(env),
             epoch: Self::get_epoch(env),
         });
-        let xlm_reward = Self::calculate_rewards(env, &position);
+        let xlm_reward = Self::calculate_rewards(env, &position)?;
         if xlm_reward > 0 {
             return Err(Error::ClaimRewardsFirst);
         }
@@ -1780,10 +2515,30 @@ impl IsStabilityPool for TokenContract {
             return Err(Error::InvalidLiquidation);
         }
 
+        // Liquidations act on prices that have cleared the oracle's security delay
+        let allow_stale = matches!(
+            OracleSettings::get(env).stale_price_policy,
+            StalePricePolicy::LiquidateFrozen
+        );
+        let prices = Self::delayed_prices(env, allow_stale)?;
+        if !matches!(
+            OracleSettings::get(env).insolvency_mode,
+            InsolvencyMode::Spot
+        ) {
+            if Self::decorate(env, cdp, lender.clone(), &prices)?.collateralization_ratio
+                >= Self::minimum_collateralization_ratio(env)
+            {
+                return Err(Error::CDPNotInsolvent);
+            }
+            Self::check_twap_insolvency(env, cdp, lender.clone())?;
+        }
+
         // Implement a safety cap for collateral used for interest
         // Convert principal debt and interest to XLM
-        let principal_debt_in_xlm = Self::convert_xasset_to_xlm(env, principal_debt)?;
-        let interest_amount_in_xlm = Self::convert_xasset_to_xlm(env, interest.amount)?;
+        let principal_debt_in_xlm =
+            Self::convert_xasset_to_xlm(principal_debt, &prices, Rounding::Bankers)?;
+        let interest_amount_in_xlm =
+            Self::convert_xasset_to_xlm(interest.amount, &prices, Rounding::Bankers)?;
 
         // Determine the maximum amount of collateral that can be used to pay interest
         let excess_collateral = collateral.saturating_sub(principal_debt_in_xlm);
@@ -1793,11 +2548,12 @@ impl IsStabilityPool for TokenContract {
         let interest_to_liquidate_xlm =
             cmp::min(interest_amount_in_xlm, max_collateral_for_interest);
         let interest_to_liquidate_xasset = if interest_amount_in_xlm > 0 {
-            bankers_round(
-                (DEFAULT_PRECISION * interest_to_liquidate_xlm * interest.amount)
-                    / interest_amount_in_xlm,
-                DEFAULT_PRECISION,
-            )
+            fixed_point::mul_div(
+                interest_to_liquidate_xlm,
+                interest.amount,
+                interest_amount_in_xlm,
+                Rounding::Bankers,
+            )?
         } else {
             0
         };
@@ -1809,9 +2565,12 @@ impl IsStabilityPool for TokenContract {
         // Update protocol accounting for interest revenue
         TokenStorage::set_interest_collected(
             env,
-            Self::get_total_interest_collected(env) + interest_to_liquidate_xlm,
+            fixed_point::add(
+                Self::get_total_interest_collected(env),
+                interest_to_liquidate_xlm,
+            )?,
         );
-        Self::increment_interest_for_current_epoch(env, &interest_to_liquidate_xlm);
+        Self::increment_interest_for_current_epoch(env, &interest_to_liquidate_xlm)?;
 
         // 2. Interest wiping after partial payment
         // If we couldn't pay all interest due to safety cap, wipe the remaining unpayable interest
@@ -1841,20 +2600,23 @@ impl IsStabilityPool for TokenContract {
         let liquidated_debt = cmp::min(principal_debt, remaining_xasset);
 
         // Calculate the proportional amount of collateral to withdraw based on principal repaid
-        let liquidated_collateral = bankers_round(
-            DEFAULT_PRECISION * collateral_less_interest * liquidated_debt / principal_debt,
-            DEFAULT_PRECISION,
-        );
+        let liquidated_collateral = fixed_point::mul_div(
+            collateral_less_interest,
+            liquidated_debt,
+            principal_debt,
+            Rounding::Bankers,
+        )?;
 
         // Update constants for the stability pool
-        Self::update_constants(env, liquidated_debt, liquidated_collateral);
+        Self::update_constants(env, liquidated_debt, liquidated_collateral)?;
 
         // Update the stability pool
         Self::subtract_total_xasset(env, liquidated_debt);
         Self::add_total_collateral(env, liquidated_collateral);
 
         // Burn the liquidated debt
-        Self::burn_internal(env, env.current_contract_address(), liquidated_debt);
+        Self::burn_internal(env, env.current_contract_address(), liquidated_debt, false);
+        Supply::add_cdp_debt(env, -liquidated_debt);
 
         // Update the CDP
         let Some(xlm_deposited) = cdp.xlm_deposited.checked_sub(liquidated_collateral) else {
@@ -1875,17 +2637,16 @@ impl IsStabilityPool for TokenContract {
                 accrued_interest_repaid: interest_to_liquidate_xasset,
                 collateral_applied_to_interest: interest_to_liquidate_xlm,
                 collateralization_ratio: calculate_collateralization_ratio(
-                    env,
-                    cdp.asset_lent + liquidated_debt,
-                    Self::lastprice_asset(env)?.price,
-                    cdp.xlm_deposited + liquidated_collateral,
-                    Self::lastprice_xlm(env)?.price,
-                    Self::decimals_xlm_feed(env)?,
-                    Self::decimals_asset_feed(env)?,
-                    interest.amount + interest_to_liquidate_xasset,
-                ),
-                xlm_price: Self::lastprice_xlm(env)?.price,
-                xasset_price: Self::lastprice_asset(env)?.price,
+                    fixed_point::add(cdp.asset_lent, liquidated_debt)?,
+                    prices.xasset,
+                    fixed_point::add(cdp.xlm_deposited, liquidated_collateral)?,
+                    prices.xlm,
+                    prices.xlm_decimals,
+                    prices.xasset_decimals,
+                    fixed_point::add(interest.amount, interest_to_liquidate_xasset)?,
+                )?,
+                xlm_price: prices.xlm,
+                xasset_price: prices.xasset,
                 ledger: env.ledger().sequence(),
                 timestamp: env.ledger().timestamp(),
             },
@@ -1927,7 +2688,7 @@ impl IsStabilityPool for TokenContract {
         let mut position = Self::get_deposit(env, to.clone())
             .unwrap_or_else(|| panic_with_error!(env, Error::StakeDoesntExist));
 
-        let xlm_reward = Self::calculate_rewards(env, &position);
+        let xlm_reward = Self::calculate_rewards(env, &position)?;
 
         let _ = Self::native(env)
             .try_transfer(&env.current_contract_address(), &to, &xlm_reward)
@@ -1944,7 +2705,7 @@ impl IsStabilityPool for TokenContract {
     /// Retrieve the current deposit amount for a given address
     fn get_staker_deposit_amount(env: &Env, address: Address) -> Result<i128, Error> {
         match Self::get_deposit(env, address) {
-            Some(position) => Ok(Self::calculate_current_deposit(env, &position)),
+            Some(position) => Self::calculate_current_deposit(env, &position),
             None => Err(Error::StakeDoesntExist),
         }
     }
@@ -1967,6 +2728,7 @@ impl IsStabilityPool for TokenContract {
         let current_state = TokenStorage::get_state(env);
 
         assert_positive(env, amount);
+        Self::check_authorized(env, &from)?;
 
         // Check if the user already has a stake
         if Self::get_deposit(env, from.clone()).is_some() {
@@ -2014,8 +2776,8 @@ impl IsStabilityPool for TokenContract {
     fn get_available_assets(env: &Env, staker: Address) -> Result<AvailableAssets, Error> {
         match Self::get_deposit(env, staker) {
             Some(position) => {
-                let d = Self::calculate_current_deposit(env, &position);
-                let xlm_reward = Self::calculate_rewards(env, &position);
+                let d = Self::calculate_current_deposit(env, &position)?;
+                let xlm_reward = Self::calculate_rewards(env, &position)?;
                 Ok(AvailableAssets {
                     available_xasset: d,
                     available_rewards: xlm_reward,